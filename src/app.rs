@@ -15,6 +15,8 @@ pub struct App<'a> {
     render_screen: bool,
     current_screen: Box<dyn Screen>,
     bg_switch_label: Label,
+    quit_label: Label,
+    confirming_quit: bool,
     config: Config,
 }
 
@@ -23,7 +25,7 @@ const MAX_FRAME_TIME: Duration = Duration::from_millis(33);
 impl<'a> App<'a> {
     pub fn new(event_loop: &EventsLoop, config: Config) -> Self {
         let mut gfx_window = GfxWindow::default_win_size(event_loop);
-        let screen = screens::Menu::new(&mut gfx_window);
+        let screen = Self::first_screen(&mut gfx_window, &config);
         let bg_switch_label = Label::new(
             32.0, // FIXME: what font size?
             gfx_window.fonts.iosevka_font_id,
@@ -31,17 +33,47 @@ impl<'a> App<'a> {
             String::from("Switch BG color"), // FIXME: icon instead?
             &mut gfx_window,
         );
+        let quit_label = Label::new(
+            32.0,
+            gfx_window.fonts.iosevka_font_id,
+            INCORRECT_WORD_COLOR,
+            String::from("Unsaved test, quit again to leave it"),
+            &mut gfx_window,
+        );
         App {
             running: true,
             gfx_window,
             mouse_position: LogicalPosition::new(0.0, 0.0),
             render_screen: true,
-            current_screen: Box::new(screen),
+            current_screen: screen,
             bg_switch_label,
+            quit_label,
+            confirming_quit: false,
             config,
         }
     }
 
+    // A test left in the journal by a crash or quitting is offered back first
+    fn first_screen(gfx_window: &mut GfxWindow, config: &Config) -> Box<dyn Screen> {
+        let entry = storage::results_path(config).and_then(|path| storage::read_journal(&path));
+        match entry {
+            Ok(Some(entry)) => Box::new(screens::ResultsScreen::recovered(entry, gfx_window)),
+            Ok(None) => Box::new(screens::Menu::new(gfx_window)),
+            Err(error) => {
+                println!("Error reading the journal: {:?}", error);
+                Box::new(screens::Menu::new(gfx_window))
+            }
+        }
+    }
+
+    fn quit(&mut self) {
+        if self.current_screen.has_unsaved_work() && !self.confirming_quit {
+            self.confirming_quit = true;
+        } else {
+            self.running = false;
+        }
+    }
+
     fn recalc_label_positions(&mut self) {
         // position bg_switch_label at the bottom of the screen
         let border_size = 35.0;
@@ -50,6 +82,8 @@ impl<'a> App<'a> {
             win_width - self.bg_switch_label.rect.bounds.x - border_size;
         self.bg_switch_label.rect.position.y =
             win_height - self.bg_switch_label.rect.bounds.y - border_size;
+        self.quit_label.rect.position.x = border_size;
+        self.quit_label.rect.position.y = self.bg_switch_label.rect.position.y;
     }
 
     fn window_resized(&mut self) {
@@ -60,6 +94,7 @@ impl<'a> App<'a> {
 
     fn mouse_click(&mut self, position: Vector2<f32>) {
         self.current_screen.mouse_click(dbg!(position));
+        self.confirming_quit = false;
 
         if self.bg_switch_label.rect.contains_point(position) {
             swap_colors();
@@ -109,7 +144,8 @@ impl<'a> App<'a> {
                         ..
                     } => {
                         if *modifiers == MOD_CMD || *modifiers == MOD_CTRL {
-                            self.running = false;
+                            self.quit();
+                            update_and_render = true;
                         }
                     }
                     _ => {}
@@ -146,6 +182,9 @@ impl<'a> App<'a> {
 
         {
             self.gfx_window.queue_label(&self.bg_switch_label);
+            if self.confirming_quit {
+                self.gfx_window.queue_label(&self.quit_label);
+            }
             self.gfx_window
                 .glyph_brush
                 .use_queue()
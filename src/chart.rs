@@ -0,0 +1,115 @@
+use crate::*;
+use cgmath::*;
+
+const MARKER_SIDES: usize = 16;
+
+// Plots (x, y) values onto a rect, for graphs and sparklines. Larger y
+// values are higher up. What's plotted goes into a mesh, to be rebuilt when
+// the values or the rect change.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Chart {
+    pub rect: Rect,
+    pub x_range: (f64, f64),
+    pub y_range: (f64, f64),
+}
+
+fn fraction(value: f64, (low, high): (f64, f64)) -> f32 {
+    if high > low {
+        ((value - low) / (high - low)) as f32
+    } else {
+        0.5 // everything is in the middle when there's nothing to spread out
+    }
+}
+
+impl Chart {
+    pub fn new(rect: Rect, x_range: (f64, f64), y_range: (f64, f64)) -> Self {
+        Self {
+            rect,
+            x_range,
+            y_range,
+        }
+    }
+
+    pub fn point(&self, x: f64, y: f64) -> Vector2<f32> {
+        vec2(
+            self.rect.position.x + fraction(x, self.x_range) * self.rect.bounds.x,
+            self.rect.position.y + (1.0 - fraction(y, self.y_range)) * self.rect.bounds.y,
+        )
+    }
+
+    fn points(&self, values: &[(f64, f64)]) -> Vec<Vector2<f32>> {
+        values.iter().map(|&(x, y)| self.point(x, y)).collect()
+    }
+
+    // The left and bottom edges
+    pub fn add_axes(&self, mesh: &mut Mesh, color: ColorArray, z: f32, width: f32) {
+        let bottom = self.rect.position.y + self.rect.bounds.y;
+        mesh.add_polyline(
+            color,
+            &[
+                self.rect.position,
+                vec2(self.rect.position.x, bottom),
+                vec2(self.rect.right_edge(), bottom),
+            ],
+            z,
+            width,
+        );
+    }
+
+    pub fn add_line(
+        &self,
+        mesh: &mut Mesh,
+        values: &[(f64, f64)],
+        color: ColorArray,
+        z: f32,
+        width: f32,
+    ) {
+        mesh.add_polyline(color, &self.points(values), z, width);
+    }
+
+    // Filled in below the line down to the bottom edge
+    pub fn add_area(&self, mesh: &mut Mesh, values: &[(f64, f64)], color: ColorArray, z: f32) {
+        let mut points = self.points(values);
+        let bottom = self.rect.position.y + self.rect.bounds.y;
+        if let (Some(first), Some(last)) = (points.first().copied(), points.last().copied()) {
+            points.push(vec2(last.x, bottom));
+            points.push(vec2(first.x, bottom));
+        }
+        mesh.add_polygon(color, &points, z);
+    }
+
+    // A dot at each value, `size` across
+    pub fn add_markers(
+        &self,
+        mesh: &mut Mesh,
+        values: &[(f64, f64)],
+        color: ColorArray,
+        z: f32,
+        size: f32,
+    ) {
+        for center in self.points(values) {
+            let circle = (0..MARKER_SIDES)
+                .map(|side| {
+                    let angle = Rad::full_turn() * side as f32 / MARKER_SIDES as f32;
+                    center + vec2(angle.cos(), angle.sin()) * size / 2.0
+                })
+                .collect::<Vec<_>>();
+            mesh.add_polygon(color, &circle, z);
+        }
+    }
+}
+
+#[test]
+fn test_values_are_placed_within_the_rect() {
+    let chart = Chart::new(
+        Rect::new(vec2(100.0, 50.0), vec2(200.0, 100.0)),
+        (0.0, 10.0),
+        (40.0, 80.0),
+    );
+    let flat = Chart::new(chart.rect, (5.0, 5.0), (60.0, 60.0));
+
+    assert_eq!(vec2(100.0, 150.0), chart.point(0.0, 40.0));
+    assert_eq!(vec2(300.0, 50.0), chart.point(10.0, 80.0));
+    assert_eq!(vec2(150.0, 75.0), chart.point(2.5, 70.0));
+    assert_eq!(vec2(200.0, 100.0), flat.point(5.0, 60.0));
+}
@@ -1,23 +1,198 @@
+use crate::TypingResult;
+use dirs::{config_dir, home_dir};
+use std::collections::HashMap;
 use std::env;
+use std::fs;
+use std::path::PathBuf;
 use std::time::Duration;
 
 const DEFAULT_DURATION_SECS: u64 = 60;
+const DEFAULT_BACKUPS_TO_KEEP: usize = 7;
+const CONFIG_FILE_NAME: &str = "wpm.conf";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StorageBackend {
+    File,
+    Sqlite,
+}
+
+impl StorageBackend {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "file" => Some(StorageBackend::File),
+            "sqlite" => Some(StorageBackend::Sqlite),
+            _ => None,
+        }
+    }
+}
+
+// Which finished tests are saved without having to click save
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AutoSave {
+    pub enabled: bool,
+    pub min_duration: Duration, // shorter tests are left for saving by hand
+    pub min_words: i32,
+}
+
+impl AutoSave {
+    pub fn should_save(&self, typing_result: &TypingResult) -> bool {
+        let words = typing_result.correct_words + typing_result.incorrect_words;
+        self.enabled
+            && words >= self.min_words
+            && typing_result
+                .duration()
+                .is_none_or(|duration| duration >= self.min_duration)
+    }
+}
 
 pub struct Config {
     pub default_test_duration: Duration,
+    pub storage_backend: StorageBackend,
+    pub results_path: Option<PathBuf>, // None means the default location in the data dir
+    pub backups_to_keep: usize,        // daily backups, zero turns them off
+    pub results_index: bool,
+    pub auto_save: AutoSave,
 }
 
 impl Config {
+    // Settings come from the config file, then environment variables override them
     pub fn new() -> Self {
+        let settings = config_file_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| parse_config_file(&contents))
+            .unwrap_or_default();
+        let setting = |env_name: &str, key: &str| {
+            env::var(env_name)
+                .ok()
+                .or_else(|| settings.get(key).cloned())
+        };
+
         let mut duration_secs = DEFAULT_DURATION_SECS;
-        if let Ok(duration_str) = env::var("WPM_TEST_DURATION") {
-            let duration_from_env: u64 = duration_str.parse().unwrap_or(0);
-            if duration_from_env > 0 {
-                duration_secs = duration_from_env;
+        if let Some(duration_str) = setting("WPM_TEST_DURATION", "test_duration") {
+            let duration_from_setting: u64 = duration_str.trim().parse().unwrap_or(0);
+            if duration_from_setting > 0 {
+                duration_secs = duration_from_setting;
             }
         }
+        let storage_backend = setting("WPM_STORAGE_BACKEND", "storage_backend")
+            .and_then(|backend_name| StorageBackend::from_name(&backend_name))
+            .unwrap_or(StorageBackend::File);
+        let results_path = setting("WPM_RESULTS_FILE", "results_file")
+            .filter(|path| !path.trim().is_empty())
+            .map(|path| expand_home_dir(path.trim()));
+        let backups_to_keep = setting("WPM_BACKUPS_TO_KEEP", "backups_to_keep")
+            .and_then(|count| count.trim().parse().ok())
+            .unwrap_or(DEFAULT_BACKUPS_TO_KEEP);
+        let results_index = setting("WPM_RESULTS_INDEX", "results_index")
+            .and_then(|enabled| parse_bool(&enabled))
+            .unwrap_or(true);
+        let auto_save = AutoSave {
+            enabled: setting("WPM_AUTO_SAVE", "auto_save")
+                .and_then(|enabled| parse_bool(&enabled))
+                .unwrap_or(false),
+            min_duration: setting("WPM_AUTO_SAVE_MIN_DURATION", "auto_save_min_duration")
+                .and_then(|secs| secs.trim().parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or_default(),
+            min_words: setting("WPM_AUTO_SAVE_MIN_WORDS", "auto_save_min_words")
+                .and_then(|words| words.trim().parse().ok())
+                .unwrap_or(0),
+        };
         Self {
             default_test_duration: Duration::from_secs(duration_secs),
+            storage_backend,
+            results_path,
+            backups_to_keep,
+            results_index,
+            auto_save,
         }
     }
 }
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn config_file_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("wpm").join(CONFIG_FILE_NAME))
+}
+
+// One `key = value` setting per line, `#` starts a comment
+fn parse_config_file(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next()?.trim();
+            let value = parts.next()?.trim();
+            if key.is_empty() {
+                None
+            } else {
+                Some((key.to_string(), value.to_string()))
+            }
+        })
+        .collect()
+}
+
+fn expand_home_dir(path: &str) -> PathBuf {
+    if let (true, Some(home)) = (path.starts_with("~/"), home_dir()) {
+        home.join(&path[2..])
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+#[test]
+fn test_parse_config_file() {
+    let contents = "
+# Where we keep our results
+results_file = ~/Sync/typing_results.wpm
+test_duration=30 # seconds
+not a setting
+ = no key
+";
+
+    let settings = parse_config_file(contents);
+
+    assert_eq!(2, settings.len());
+    assert_eq!(
+        Some(&String::from("~/Sync/typing_results.wpm")),
+        settings.get("results_file")
+    );
+    assert_eq!(Some(&String::from("30")), settings.get("test_duration"));
+}
+
+#[test]
+fn test_auto_save_skips_short_tests() {
+    let auto_save = AutoSave {
+        enabled: true,
+        min_duration: Duration::from_secs(30),
+        min_words: 10,
+    };
+    let typing_result = TypingResult {
+        correct_words: 40,
+        incorrect_words: 2,
+        started_at_ms: 1556223199000,
+        ended_at_ms: 1556223259000,
+        ..TypingResult::default()
+    };
+    let short_test = TypingResult {
+        started_at_ms: 1556223249000,
+        ..typing_result.clone()
+    };
+    let few_words = TypingResult {
+        correct_words: 5,
+        incorrect_words: 4,
+        ..typing_result.clone()
+    };
+
+    assert!(auto_save.should_save(&typing_result));
+    assert!(!auto_save.should_save(&short_test));
+    assert!(!auto_save.should_save(&few_words));
+    assert!(!AutoSave::default().should_save(&typing_result));
+}
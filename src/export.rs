@@ -0,0 +1,327 @@
+use crate::*;
+use chrono::prelude::{DateTime, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::Path;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Self::from_name)
+    }
+}
+
+// How a result looks in an export. The ID is written as hex so spreadsheets
+// don't round it off, and a blank ID gets a new one on import.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct ExportRecord {
+    id: String,
+    time: u64,
+    wpm: i32,
+    correct_words: i32,
+    incorrect_words: i32,
+    backspaces: i32,
+    notes: String,
+    started_at_ms: u64,
+    ended_at_ms: u64,
+    utc_offset_secs: i32,
+    tags: String, // separated by commas, spreadsheets can't hold a list in a cell
+    timeline: String,
+    keystrokes: String,
+    missed_words: String,
+}
+
+impl From<&TypingResult> for ExportRecord {
+    fn from(typing_result: &TypingResult) -> Self {
+        Self {
+            id: format!("{:016x}", typing_result.id),
+            time: typing_result.time,
+            wpm: typing_result.wpm,
+            correct_words: typing_result.correct_words,
+            incorrect_words: typing_result.incorrect_words,
+            backspaces: typing_result.backspaces,
+            notes: typing_result.notes.clone(),
+            started_at_ms: typing_result.started_at_ms,
+            ended_at_ms: typing_result.ended_at_ms,
+            utc_offset_secs: typing_result.utc_offset_secs,
+            tags: typing_result.tags_text(),
+            timeline: typing_result.timeline_text(),
+            keystrokes: typing_result.keystrokes_text(),
+            missed_words: typing_result.missed_words_text(),
+        }
+    }
+}
+
+impl ExportRecord {
+    fn into_typing_result(self) -> Result<TypingResult, Box<dyn Error>> {
+        let id = if self.id.trim().is_empty() {
+            new_result_id()
+        } else {
+            u64::from_str_radix(self.id.trim(), 16)
+                .map_err(|err| format!("Bad result ID {:?}: {}", self.id, err))?
+        };
+        Ok(TypingResult {
+            id,
+            correct_words: self.correct_words,
+            incorrect_words: self.incorrect_words,
+            backspaces: self.backspaces,
+            wpm: self.wpm,
+            time: self.time,
+            notes: self.notes,
+            started_at_ms: self.started_at_ms,
+            ended_at_ms: self.ended_at_ms,
+            utc_offset_secs: self.utc_offset_secs,
+            tags: parse_tags(&self.tags),
+            timeline: parse_timeline(&self.timeline)?,
+            keystrokes: parse_keystrokes(&self.keystrokes)?,
+            missed_words: self
+                .missed_words
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+        })
+    }
+}
+
+pub fn export_results<W: Write>(
+    wr: W,
+    results: &[TypingResult],
+    format: ExportFormat,
+) -> Result<(), Box<dyn Error>> {
+    let records = results.iter().map(ExportRecord::from);
+    match format {
+        ExportFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(wr);
+            for record in records {
+                csv_writer.serialize(record)?;
+            }
+            csv_writer.flush()?;
+        }
+        ExportFormat::Json => {
+            let mut wr = wr;
+            serde_json::to_writer_pretty(&mut wr, &records.collect::<Vec<_>>())?;
+            writeln!(wr)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn import_results<R: Read>(
+    rd: R,
+    format: ExportFormat,
+) -> Result<Vec<TypingResult>, Box<dyn Error>> {
+    let records: Vec<ExportRecord> = match format {
+        ExportFormat::Csv => csv::Reader::from_reader(rd)
+            .deserialize()
+            .collect::<Result<_, _>>()?,
+        ExportFormat::Json => serde_json::from_reader(rd)?,
+    };
+    records
+        .into_iter()
+        .map(ExportRecord::into_typing_result)
+        .collect()
+}
+
+// Aligned columns for reading in a terminal, in the order given
+pub fn write_table<W: Write>(mut wr: W, results: &[TypingResult]) -> Result<(), Box<dyn Error>> {
+    let header = [
+        "Date",
+        "WPM",
+        "Correct",
+        "Incorrect",
+        "Backspaces",
+        "Tags",
+        "Notes",
+    ];
+    let numeric = |column: usize| (1..=4).contains(&column);
+    let rows = results
+        .iter()
+        .map(|typing_result| {
+            vec![
+                typing_result
+                    .datetime()
+                    .map(|datetime| datetime.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default(),
+                typing_result.wpm.to_string(),
+                typing_result.correct_words.to_string(),
+                typing_result.incorrect_words.to_string(),
+                typing_result.backspaces.to_string(),
+                typing_result.tags_text(),
+                typing_result.notes.lines().collect::<Vec<_>>().join(" "),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let mut widths = header
+        .iter()
+        .map(|heading| heading.chars().count())
+        .collect::<Vec<_>>();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = header.iter().map(|heading| heading.to_string()).collect();
+    for row in std::iter::once(header).chain(rows) {
+        let cells = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, (cell, &width))| {
+                if numeric(column) {
+                    format!("{:>width$}", cell, width = width)
+                } else {
+                    format!("{:width$}", cell, width = width)
+                }
+            })
+            .collect::<Vec<_>>();
+        writeln!(wr, "{}", cells.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+// A date given on the command line, either `2019-04-25` for midnight local
+// time or a full RFC 3339 time, as seconds since the epoch
+pub fn parse_date(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let datetime = match NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        Ok(date) => Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()?
+            .timestamp(),
+        Err(_) => DateTime::parse_from_rfc3339(text).ok()?.timestamp(),
+    };
+    if datetime < 0 {
+        None
+    } else {
+        Some(datetime as u64)
+    }
+}
+
+#[cfg(test)]
+fn example_results() -> Vec<TypingResult> {
+    vec![
+        TypingResult {
+            id: 0x1234_5678_9abc_def0,
+            correct_words: 87,
+            incorrect_words: 3,
+            backspaces: 2,
+            wpm: 87,
+            time: 1556223259,
+            notes: String::from("Notes with \"quotes\", commas\nand a newline"),
+            started_at_ms: 1556223199123,
+            ended_at_ms: 1556223259123,
+            utc_offset_secs: 3600,
+            tags: vec![String::from("ergodox"), String::from("warmup")],
+            timeline: vec![
+                WpmSample {
+                    raw_wpm: 91.2,
+                    net_wpm: 60.0,
+                },
+                WpmSample {
+                    raw_wpm: 86.4,
+                    net_wpm: 90.0,
+                },
+            ],
+            keystrokes: vec![
+                Keystroke {
+                    expected: Some(','),
+                    typed: ':',
+                    interval_ms: 0,
+                },
+                Keystroke {
+                    expected: None,
+                    typed: ' ',
+                    interval_ms: 1234,
+                },
+            ],
+            missed_words: vec![String::from("quotes,"), String::from("newline")],
+        },
+        TypingResult {
+            id: u64::MAX,
+            correct_words: 60,
+            wpm: 60,
+            time: 1556223359,
+            ..TypingResult::default()
+        },
+    ]
+}
+
+#[test]
+fn test_results_round_trip_through_csv() {
+    let mut buffer = Vec::new();
+
+    export_results(&mut buffer, &example_results(), ExportFormat::Csv).unwrap();
+    let imported = import_results(&buffer[..], ExportFormat::Csv).unwrap();
+
+    assert_eq!(example_results(), imported);
+}
+
+#[test]
+fn test_results_round_trip_through_json() {
+    let mut buffer = Vec::new();
+
+    export_results(&mut buffer, &example_results(), ExportFormat::Json).unwrap();
+    let imported = import_results(&buffer[..], ExportFormat::Json).unwrap();
+
+    assert_eq!(example_results(), imported);
+}
+
+#[test]
+fn test_import_gives_blank_ids_a_new_id_and_skips_duplicates() {
+    let csv = "id,time,wpm,correct_words,incorrect_words,backspaces,notes
+,1556223259,87,87,3,2,same test as an existing one
+,1556229999,70,70,1,0,new test
+,1556229999,70,70,1,0,new test
+";
+
+    let imported = import_results(csv.as_bytes(), ExportFormat::Csv).unwrap();
+    assert!(imported.iter().all(|typing_result| typing_result.id != 0));
+
+    let (unseen, duplicates) = unseen_results(&example_results(), imported);
+
+    assert_eq!(2, duplicates);
+    assert_eq!(1, unseen.len());
+    assert_eq!("new test", unseen[0].notes);
+}
+
+#[test]
+fn test_table_columns_line_up() {
+    let mut buffer = Vec::new();
+
+    write_table(&mut buffer, &example_results()).unwrap();
+    let table = String::from_utf8(buffer).unwrap();
+    let lines = table.lines().collect::<Vec<_>>();
+
+    assert_eq!(3, lines.len());
+    assert!(lines[0].starts_with("Date "));
+    assert!(lines[1].contains("ergodox, warmup  Notes with \"quotes\", commas and a newline"));
+    let wpm_end = lines[0].find("WPM").unwrap() + "WPM".len();
+    assert_eq!("87", &lines[1][wpm_end - 2..wpm_end]);
+    assert_eq!("60", &lines[2][wpm_end - 2..wpm_end]);
+}
+
+#[test]
+fn test_dates_parse_as_days_or_full_times() {
+    assert_eq!(Some(1556223259), parse_date("2019-04-25T20:14:19+00:00"));
+    assert_eq!(Some(1556223259), parse_date("2019-04-25T21:14:19+01:00"));
+    assert!(parse_date("2019-04-25").is_some());
+    assert_eq!(None, parse_date("last tuesday"));
+}
@@ -9,6 +9,18 @@ use glutin::Api::OpenGl;
 use glutin::*;
 use std::error::Error;
 
+const INITIAL_MESH_LEN: usize = 4096; // vertices or indices, enough for most charts
+
+fn dynamic_buffer<T>(
+    factory: &mut gfx_device_gl::Factory,
+    role: buffer::Role,
+    len: usize,
+) -> handle::Buffer<gfx_device_gl::Resources, T> {
+    factory
+        .create_buffer(len, role, memory::Usage::Dynamic, memory::Bind::empty())
+        .expect("dynamic buffer creation to work")
+}
+
 pub struct GfxWindow<'a> {
     pub logical_size: LogicalSize,
     pub physical_size: PhysicalSize,
@@ -20,6 +32,12 @@ pub struct GfxWindow<'a> {
     pub device: gfx_device_gl::Device,
     pub quad_bundle:
         pso::bundle::Bundle<gfx_device_gl::Resources, pipe::Data<gfx_device_gl::Resources>>,
+    pub line_pso: PipelineState<gfx_device_gl::Resources, line_pipe::Meta>,
+    pub line_locals: handle::Buffer<gfx_device_gl::Resources, LineLocals>,
+    // meshes are uploaded into these, which are only replaced to grow them
+    pub line_vbuf: handle::Buffer<gfx_device_gl::Resources, LineVertex>,
+    pub line_ibuf: handle::Buffer<gfx_device_gl::Resources, u32>,
+    pub factory: gfx_device_gl::Factory,
     pub glyph_brush: GlyphBrush<'a, gfx_device_gl::Resources, gfx_device_gl::Factory>,
     pub encoder: Encoder<gfx_device_gl::Resources, gfx_device_gl::CommandBuffer>,
 }
@@ -73,6 +91,17 @@ impl<'a> GfxWindow<'a> {
         };
         let quad_bundle = pso::bundle::Bundle::new(quad_slice, quad_pso, quad_data);
 
+        let line_pso = factory
+            .create_pipeline_simple(
+                include_bytes!("shaders/line_150_core.vert"),
+                include_bytes!("shaders/quad_150_core.frag"),
+                line_pipe::new(),
+            )
+            .expect("line pso construction to work");
+        let line_locals = factory.create_constant_buffer(1);
+        let line_vbuf = dynamic_buffer(&mut factory, buffer::Role::Vertex, INITIAL_MESH_LEN);
+        let line_ibuf = dynamic_buffer(&mut factory, buffer::Role::Index, INITIAL_MESH_LEN);
+
         let mut fonts = Fonts::default();
 
         let mut glyph_brush = GlyphBrushBuilder::using_font_bytes(include_bytes!(
@@ -97,6 +126,11 @@ impl<'a> GfxWindow<'a> {
             window,
             device,
             quad_bundle,
+            line_pso,
+            line_locals,
+            line_vbuf,
+            line_ibuf,
+            factory,
             glyph_brush,
             encoder,
         }
@@ -150,6 +184,78 @@ impl<'a> GfxWindow<'a> {
         self.draw_quad(color, rect, z);
     }
 
+    // `width` is in pixels, like the points
+    pub fn draw_line(
+        &mut self,
+        color: [f32; 4],
+        from: Vector2<f32>,
+        to: Vector2<f32>,
+        z: f32,
+        width: f32,
+    ) {
+        self.draw_polyline(color, &[from, to], z, width);
+    }
+
+    // These tessellate on every call, anything drawn every frame should be
+    // kept in a Mesh instead
+    pub fn draw_polyline(&mut self, color: [f32; 4], points: &[Vector2<f32>], z: f32, width: f32) {
+        let mut mesh = Mesh::default();
+        mesh.add_polyline(color, points, z, width);
+        self.draw_mesh(&mesh);
+    }
+
+    // Any simple polygon, convex or not, wound either way
+    pub fn draw_polygon(&mut self, color: [f32; 4], points: &[Vector2<f32>], z: f32) {
+        let mut mesh = Mesh::default();
+        mesh.add_polygon(color, points, z);
+        self.draw_mesh(&mesh);
+    }
+
+    pub fn draw_mesh(&mut self, mesh: &Mesh) {
+        if mesh.indices.is_empty() {
+            return;
+        }
+        if mesh.vertices.len() > self.line_vbuf.len() {
+            let len = mesh.vertices.len().next_power_of_two();
+            self.line_vbuf = dynamic_buffer(&mut self.factory, buffer::Role::Vertex, len);
+        }
+        if mesh.indices.len() > self.line_ibuf.len() {
+            let len = mesh.indices.len().next_power_of_two();
+            self.line_ibuf = dynamic_buffer(&mut self.factory, buffer::Role::Index, len);
+        }
+        self.encoder
+            .update_buffer(&self.line_vbuf, &mesh.vertices, 0)
+            .expect("mesh vertices to fit the buffer");
+        self.encoder
+            .update_buffer(&self.line_ibuf, &mesh.indices, 0)
+            .expect("mesh indices to fit the buffer");
+
+        let data = line_pipe::Data {
+            vbuf: self.line_vbuf.clone(),
+            locals: self.line_locals.clone(),
+            out_color: self.quad_bundle.data.out_color.clone(),
+            out_depth: self.quad_bundle.data.out_depth.clone(),
+        };
+        let window_dim = self.window_dim().into();
+        for part in &mesh.parts {
+            let slice = Slice {
+                start: part.indices.start,
+                end: part.indices.end,
+                base_vertex: 0,
+                instances: None,
+                buffer: IndexBuffer::Index32(self.line_ibuf.clone()),
+            };
+            let locals = LineLocals {
+                color: part.color,
+                window_dim,
+                z: part.z,
+            };
+            self.encoder
+                .update_constant_buffer(&self.line_locals, &locals);
+            self.encoder.draw(&slice, &self.line_pso, &data);
+        }
+    }
+
     pub fn back_label(&mut self) -> Label {
         let mut label = Label::new(
             UI_TEXT_BUTTON_SIZE,
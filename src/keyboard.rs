@@ -0,0 +1,93 @@
+// A US QWERTY keyboard, for drawing the key heatmap and telling which finger
+// types each key. Each row starts further right than the one above.
+pub const KEY_ROWS: [&str; 4] = [
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Finger {
+    LeftPinky,
+    LeftRing,
+    LeftMiddle,
+    LeftIndex,
+    Thumbs,
+    RightIndex,
+    RightMiddle,
+    RightRing,
+    RightPinky,
+}
+
+impl Finger {
+    pub fn name(self) -> &'static str {
+        match self {
+            Finger::LeftPinky => "left pinky",
+            Finger::LeftRing => "left ring",
+            Finger::LeftMiddle => "left middle",
+            Finger::LeftIndex => "left index",
+            Finger::Thumbs => "thumbs",
+            Finger::RightIndex => "right index",
+            Finger::RightMiddle => "right middle",
+            Finger::RightRing => "right ring",
+            Finger::RightPinky => "right pinky",
+        }
+    }
+}
+
+// Touch typing, from the leftmost key of a letter row
+const FINGERS_BY_COLUMN: [Finger; 10] = [
+    Finger::LeftPinky,
+    Finger::LeftRing,
+    Finger::LeftMiddle,
+    Finger::LeftIndex,
+    Finger::LeftIndex,
+    Finger::RightIndex,
+    Finger::RightIndex,
+    Finger::RightMiddle,
+    Finger::RightRing,
+    Finger::RightPinky,
+];
+
+// The key a character is typed with, shifted characters on the same key as
+// the unshifted ones
+pub fn key_for(typed: char) -> char {
+    const SHIFTED: &str = "~!@#$%^&*()_+{}|:\"<>?";
+    const UNSHIFTED: &str = "`1234567890-=[]\\;',./";
+    match SHIFTED.chars().position(|shifted| shifted == typed) {
+        Some(idx) => UNSHIFTED.chars().nth(idx).unwrap_or(typed),
+        None => typed.to_lowercase().next().unwrap_or(typed),
+    }
+}
+
+// None for keys that aren't on the keyboard, like accented letters
+pub fn finger(key: char) -> Option<Finger> {
+    if key == ' ' {
+        return Some(Finger::Thumbs);
+    }
+    KEY_ROWS.iter().enumerate().find_map(|(row, keys)| {
+        let column = keys.chars().position(|row_key| row_key == key)?;
+        // the number row has an extra key on the left
+        let column = if row == 0 {
+            column.saturating_sub(1)
+        } else {
+            column
+        };
+        Some(FINGERS_BY_COLUMN[column.min(FINGERS_BY_COLUMN.len() - 1)])
+    })
+}
+
+#[test]
+fn test_keys_are_typed_with_their_touch_typing_finger() {
+    assert_eq!(Some(Finger::LeftPinky), finger('q'));
+    assert_eq!(Some(Finger::LeftIndex), finger('g'));
+    assert_eq!(Some(Finger::RightIndex), finger('n'));
+    assert_eq!(Some(Finger::RightRing), finger('9'));
+    assert_eq!(Some(Finger::RightPinky), finger('\''));
+    assert_eq!(Some(Finger::LeftPinky), finger('`'));
+    assert_eq!(Some(Finger::Thumbs), finger(' '));
+    assert_eq!(None, finger('é'));
+    assert_eq!(Some(Finger::RightPinky), finger(key_for('?')));
+    assert_eq!('a', key_for('A'));
+}
@@ -1,39 +1,79 @@
+#[cfg(feature = "gui")]
 mod animation;
+#[cfg(feature = "gui")]
 pub mod app;
+#[cfg(feature = "gui")]
+mod chart;
 mod colours;
 pub mod config;
+pub mod export;
+#[cfg(feature = "gui")]
 mod fonts;
+#[cfg(feature = "gui")]
 mod gfx_window;
+#[cfg(feature = "gui")]
 mod input;
+pub mod keyboard;
+#[cfg(feature = "gui")]
 mod label;
+#[cfg(feature = "gui")]
 mod layout;
+#[cfg(feature = "gui")]
+mod line;
+#[cfg(feature = "gui")]
 mod maths;
+pub mod practice;
+#[cfg(feature = "gui")]
 mod quad;
+#[cfg(feature = "gui")]
 mod rect;
+#[cfg(feature = "gui")]
 mod screen;
+#[cfg(feature = "gui")]
 mod screens;
+pub mod stats;
 pub mod storage;
+pub mod tui;
 mod typing_result;
+#[cfg(feature = "gui")]
 mod typing_state;
 mod typing_test;
+#[cfg(feature = "gui")]
 mod ui_state;
 mod words;
 
+#[cfg(feature = "gui")]
 pub use animation::Animation;
+#[cfg(feature = "gui")]
 pub use app::App;
+#[cfg(feature = "gui")]
+pub use chart::Chart;
 pub use colours::*;
 pub use config::Config;
+#[cfg(feature = "gui")]
 pub use fonts::*;
+#[cfg(feature = "gui")]
 pub use gfx_window::GfxWindow;
+#[cfg(feature = "gui")]
 pub use input::*;
+#[cfg(feature = "gui")]
 pub use label::Label;
+#[cfg(feature = "gui")]
+pub use line::*;
+#[cfg(feature = "gui")]
 pub use maths::*;
+#[cfg(feature = "gui")]
 pub use quad::*;
+#[cfg(feature = "gui")]
 pub use rect::Rect;
+#[cfg(feature = "gui")]
 pub use screen::Screen;
 pub use typing_result::*;
+#[cfg(feature = "gui")]
 pub use typing_state::TypingState;
 pub use typing_test::{EnteredWord, TypingTest};
+#[cfg(feature = "gui")]
 pub use ui_state::UIState;
 
+#[cfg(feature = "gui")]
 pub const UI_TEXT_BUTTON_SIZE: f32 = 68.0;
@@ -0,0 +1,367 @@
+use cgmath::*;
+use gfx::{self, *};
+use std::ops::Range;
+
+use crate::quad::{ColorFormat, DepthFormat};
+
+// Lines and polygons are tessellated on the CPU into triangles in window
+// pixels. Their edges get an extra pixel wide fringe that fades to nothing.
+gfx_defines! {
+  vertex LineVertex {
+    pos: [f32; 2] = "a_Pos",
+    alpha: f32 = "a_Alpha",
+  }
+
+  constant LineLocals {
+    color: [f32; 4] = "u_Color",
+    window_dim: [f32; 2] = "u_WindowDim",
+    z: f32 = "u_Z",
+  }
+
+  pipeline line_pipe {
+    vbuf: VertexBuffer<LineVertex> = (),
+    locals: ConstantBuffer<LineLocals> = "Locals",
+    out_color: BlendTarget<ColorFormat> = ("Target0", state::ColorMask::all(), preset::blend::ALPHA),
+    // the faded fringe mustn't hide what's drawn behind it later
+    out_depth: DepthTarget<DepthFormat> = preset::depth::LESS_EQUAL_TEST,
+  }
+}
+
+const FRINGE: f32 = 1.0;
+const MITER_LIMIT: f32 = 4.0; // sharper corners than this are cut short
+
+fn vertex(pos: Vector2<f32>, alpha: f32) -> LineVertex {
+    LineVertex {
+        pos: pos.into(),
+        alpha,
+    }
+}
+
+fn normal(from: Vector2<f32>, to: Vector2<f32>) -> Vector2<f32> {
+    let direction = to - from;
+    if direction.magnitude2() == 0.0 {
+        vec2(0.0, 0.0)
+    } else {
+        let direction = direction.normalize();
+        vec2(-direction.y, direction.x)
+    }
+}
+
+// How far along the normal at each point, and in which direction, the edges
+// go for a width of 1, so that segments meet at a mitred corner
+fn miters(points: &[Vector2<f32>], closed: bool) -> Vec<Vector2<f32>> {
+    let len = points.len();
+    (0..len)
+        .map(|idx| {
+            let before = if idx > 0 {
+                Some(normal(points[idx - 1], points[idx]))
+            } else if closed {
+                Some(normal(points[len - 1], points[idx]))
+            } else {
+                None
+            };
+            let after = if idx + 1 < len {
+                Some(normal(points[idx], points[idx + 1]))
+            } else if closed {
+                Some(normal(points[idx], points[0]))
+            } else {
+                None
+            };
+            match (before, after) {
+                (Some(before), Some(after)) => {
+                    let sum = before + after;
+                    if sum.magnitude2() == 0.0 {
+                        return before;
+                    }
+                    let miter = sum.normalize();
+                    let scale = 1.0 / miter.dot(after).max(1.0 / MITER_LIMIT);
+                    miter * scale
+                }
+                (Some(normal), None) | (None, Some(normal)) => normal,
+                (None, None) => vec2(0.0, 0.0),
+            }
+        })
+        .collect()
+}
+
+// Joins two rows of vertices across the line with quads
+fn join_rows(indices: &mut Vec<u32>, first_row: u32, second_row: u32, row_len: u32) {
+    for column in 0..row_len - 1 {
+        let (a, b) = (first_row + column, second_row + column);
+        indices.extend_from_slice(&[a, a + 1, b + 1, b + 1, b, a]);
+    }
+}
+
+pub fn tessellate_polyline(
+    points: &[Vector2<f32>],
+    width: f32,
+    closed: bool,
+) -> (Vec<LineVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    if points.len() < 2 {
+        return (vertices, indices);
+    }
+    let half_width = width / 2.0;
+    let core = (half_width - FRINGE / 2.0).max(0.0);
+    let outer = half_width + FRINGE / 2.0;
+    let core_alpha = width.min(1.0); // hairlines are drawn fainter rather than thinner
+
+    // each point has a row of four across the line: fringe, core, core, fringe
+    for (&point, &miter) in points.iter().zip(&miters(points, closed)) {
+        vertices.push(vertex(point - miter * outer, 0.0));
+        vertices.push(vertex(point - miter * core, core_alpha));
+        vertices.push(vertex(point + miter * core, core_alpha));
+        vertices.push(vertex(point + miter * outer, 0.0));
+    }
+    let rows = points.len() as u32;
+    for row in 0..rows - 1 {
+        join_rows(&mut indices, row * 4, (row + 1) * 4, 4);
+    }
+    if closed {
+        join_rows(&mut indices, (rows - 1) * 4, 0, 4);
+    }
+    (vertices, indices)
+}
+
+fn signed_area(points: &[Vector2<f32>]) -> f32 {
+    let len = points.len();
+    (0..len)
+        .map(|idx| {
+            let (a, b) = (points[idx], points[(idx + 1) % len]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+fn cross(o: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+fn in_triangle(point: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) -> bool {
+    cross(a, b, point) >= 0.0 && cross(b, c, point) >= 0.0 && cross(c, a, point) >= 0.0
+}
+
+// Triangles covering a simple polygon, by ear clipping. `points` must wind
+// with a positive area.
+fn triangulate(points: &[Vector2<f32>]) -> Vec<u32> {
+    let mut remaining = (0..points.len() as u32).collect::<Vec<_>>();
+    let mut indices = Vec::new();
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let is_ear = |idx: usize| {
+            let (a, b, c) = (
+                remaining[(idx + len - 1) % len],
+                remaining[idx],
+                remaining[(idx + 1) % len],
+            );
+            let (pa, pb, pc) = (points[a as usize], points[b as usize], points[c as usize]);
+            cross(pa, pb, pc) > 0.0
+                && remaining
+                    .iter()
+                    .filter(|&&other| other != a && other != b && other != c)
+                    .all(|&other| !in_triangle(points[other as usize], pa, pb, pc))
+        };
+        // a polygon that crosses itself might not have one, so clip anyway
+        let ear = (0..len).find(|&idx| is_ear(idx)).unwrap_or(0);
+        indices.extend_from_slice(&[
+            remaining[(ear + len - 1) % len],
+            remaining[ear],
+            remaining[(ear + 1) % len],
+        ]);
+        remaining.remove(ear);
+    }
+    if remaining.len() == 3 {
+        indices.extend_from_slice(&remaining);
+    }
+    indices
+}
+
+pub fn tessellate_polygon(points: &[Vector2<f32>]) -> (Vec<LineVertex>, Vec<u32>) {
+    if points.len() < 3 {
+        return (Vec::new(), Vec::new());
+    }
+    let mut points = points.to_vec();
+    if signed_area(&points) < 0.0 {
+        points.reverse();
+    }
+    let len = points.len() as u32;
+    // with a positive area the normals point inwards
+    let mut vertices = points
+        .iter()
+        .map(|&point| vertex(point, 1.0))
+        .collect::<Vec<_>>();
+    let mut indices = triangulate(&points);
+    for (&point, &miter) in points.iter().zip(&miters(&points, true)) {
+        vertices.push(vertex(point - miter * FRINGE, 0.0));
+    }
+    for idx in 0..len {
+        let next = (idx + 1) % len;
+        let (inner, next_inner) = (idx, next);
+        let (outer, next_outer) = (len + idx, len + next);
+        indices.extend_from_slice(&[inner, outer, next_outer, next_outer, next_inner, inner]);
+    }
+    (vertices, indices)
+}
+
+// A run of the mesh's indices drawn in one colour at one depth
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshPart {
+    pub color: [f32; 4],
+    pub z: f32,
+    pub indices: Range<u32>,
+}
+
+// Lines and polygons tessellated once and kept, so that drawing them each
+// frame is only an upload. Rebuild it when what it shows changes.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<LineVertex>,
+    pub indices: Vec<u32>,
+    pub parts: Vec<MeshPart>,
+}
+
+impl Mesh {
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.parts.clear();
+    }
+
+    // `width` is in pixels, like the points
+    pub fn add_polyline(&mut self, color: [f32; 4], points: &[Vector2<f32>], z: f32, width: f32) {
+        let (vertices, indices) = tessellate_polyline(points, width, false);
+        self.add(color, z, &vertices, &indices);
+    }
+
+    // Any simple polygon, convex or not, wound either way
+    pub fn add_polygon(&mut self, color: [f32; 4], points: &[Vector2<f32>], z: f32) {
+        let (vertices, indices) = tessellate_polygon(points);
+        self.add(color, z, &vertices, &indices);
+    }
+
+    fn add(&mut self, color: [f32; 4], z: f32, vertices: &[LineVertex], indices: &[u32]) {
+        if indices.is_empty() {
+            return;
+        }
+        let base_vertex = self.vertices.len() as u32;
+        let start = self.indices.len() as u32;
+        self.vertices.extend_from_slice(vertices);
+        self.indices
+            .extend(indices.iter().map(|&idx| base_vertex + idx));
+        let end = self.indices.len() as u32;
+        // shapes added one after another in the same colour are drawn together
+        match self.parts.last_mut() {
+            Some(part) if part.color == color && part.z == z && part.indices.end == start => {
+                part.indices.end = end;
+            }
+            _ => self.parts.push(MeshPart {
+                color,
+                z,
+                indices: start..end,
+            }),
+        }
+    }
+}
+
+#[test]
+fn test_polyline_has_a_faded_fringe_either_side() {
+    let (vertices, indices) =
+        tessellate_polyline(&[vec2(0.0, 10.0), vec2(100.0, 10.0)], 3.0, false);
+
+    assert_eq!(8, vertices.len());
+    assert_eq!(3 * 6, indices.len());
+    let across = vertices[..4]
+        .iter()
+        .map(|vertex| (vertex.pos[1], vertex.alpha))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![(8.0, 0.0), (9.0, 1.0), (11.0, 1.0), (12.0, 0.0)],
+        across
+    );
+}
+
+#[test]
+fn test_polyline_corners_are_mitred() {
+    let (vertices, _) = tessellate_polyline(
+        &[vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(10.0, 10.0)],
+        2.0,
+        false,
+    );
+
+    // the outside of the corner is pushed out diagonally to meet both edges
+    let corner = vertices[4].pos;
+    assert!((corner[0] - 11.5).abs() < 1e-5);
+    assert!((corner[1] + 1.5).abs() < 1e-5);
+}
+
+#[test]
+fn test_polygon_is_filled_with_triangles_and_a_fringe() {
+    // an L shape, which isn't convex, wound the other way round
+    let points = [
+        vec2(0.0, 0.0),
+        vec2(0.0, 20.0),
+        vec2(20.0, 20.0),
+        vec2(20.0, 10.0),
+        vec2(10.0, 10.0),
+        vec2(10.0, 0.0),
+    ];
+
+    let (vertices, indices) = tessellate_polygon(&points);
+
+    assert_eq!(12, vertices.len());
+    let fill = &indices[..(points.len() - 2) * 3];
+    let fill_area = fill
+        .chunks(3)
+        .map(|triangle| {
+            let corner = |idx: u32| Vector2::from(vertices[idx as usize].pos);
+            cross(
+                corner(triangle[0]),
+                corner(triangle[1]),
+                corner(triangle[2]),
+            ) / 2.0
+        })
+        .sum::<f32>();
+    assert_eq!(300.0, fill_area);
+    assert_eq!(fill.len() + points.len() * 6, indices.len());
+    assert!(vertices[6..].iter().all(|vertex| vertex.alpha == 0.0));
+}
+
+#[test]
+fn test_mesh_draws_shapes_of_the_same_colour_together() {
+    let (red, blue) = ([1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]);
+    let triangle = [vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(0.0, 10.0)];
+    let line = [vec2(0.0, 0.0), vec2(10.0, 10.0)];
+    let (triangle_vertices, triangle_indices) = tessellate_polygon(&triangle);
+    let line_indices = tessellate_polyline(&line, 2.0, false).1;
+
+    let mut mesh = Mesh::default();
+    mesh.add_polygon(red, &triangle, 0.5);
+    mesh.add_polygon(red, &triangle, 0.5);
+    mesh.add_polyline(blue, &line, 0.5, 2.0);
+    mesh.add_polyline(blue, &[vec2(0.0, 0.0)], 0.5, 2.0); // too short to draw
+
+    let polygons_end = 2 * triangle_indices.len() as u32;
+    assert_eq!(
+        vec![
+            MeshPart {
+                color: red,
+                z: 0.5,
+                indices: 0..polygons_end,
+            },
+            MeshPart {
+                color: blue,
+                z: 0.5,
+                indices: polygons_end..polygons_end + line_indices.len() as u32,
+            },
+        ],
+        mesh.parts
+    );
+    // the second triangle's indices point at its own vertices
+    let second = triangle_indices.len();
+    assert_eq!(
+        triangle_indices[0] + triangle_vertices.len() as u32,
+        mesh.indices[second]
+    );
+}
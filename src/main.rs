@@ -1,42 +1,756 @@
-#![windows_subsystem = "windows"]
+#![cfg_attr(feature = "gui", windows_subsystem = "windows")]
 
 use clap;
+#[cfg(feature = "gui")]
 use glutin::EventsLoop;
+use std::env;
 use std::error::Error;
-use wpm::{config, storage, App};
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use wpm::config::StorageBackend;
+use wpm::export::{self, ExportFormat};
+#[cfg(feature = "gui")]
+use wpm::App;
+use wpm::{config, keyboard, stats, storage};
 
-fn run_gui() -> Result<(), Box<dyn Error>> {
+#[cfg(feature = "gui")]
+fn run_gui(config: config::Config) -> Result<(), Box<dyn Error>> {
     let mut event_loop = EventsLoop::new();
-    let config = config::Config::new();
     let mut app = App::new(&event_loop, config);
     app.run(&mut event_loop)?;
 
     Ok(())
 }
 
-fn print_results() -> Result<(), Box<dyn Error>> {
-    match storage::read_results_from_file() {
+// Built without the window, so take the test in the terminal instead
+#[cfg(not(feature = "gui"))]
+fn run_gui(config: config::Config) -> Result<(), Box<dyn Error>> {
+    wpm::tui::run(&config, false)
+}
+
+fn print_results(config: &config::Config, args: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    // the arguments have been through their validators already
+    let date_arg = |name| args.value_of(name).and_then(export::parse_date);
+    let limit = args.value_of("limit").and_then(|limit| limit.parse().ok());
+    let sort_by_wpm = args.value_of("sort") == Some("wpm");
+    let query = storage::ResultsQuery {
+        since: date_arg("since"),
+        until: date_arg("until"),
+        min_wpm: args.value_of("min-wpm").and_then(|wpm| wpm.parse().ok()),
+        // the query keeps the newest, so the best have to be picked out here
+        limit: if sort_by_wpm { None } else { limit },
+        tag: args.value_of("tag").map(String::from),
+        ..storage::ResultsQuery::default()
+    };
+    let mut results = query_results(config, &query);
+    if sort_by_wpm {
+        results.sort_by_key(|typing_result| {
+            (
+                std::cmp::Reverse(typing_result.wpm),
+                std::cmp::Reverse(typing_result.timestamp_ms()),
+            )
+        });
+        results.truncate(limit.unwrap_or(results.len()));
+    } else {
+        results.sort_by_key(|typing_result| typing_result.timestamp_ms());
+    }
+
+    let stdout = io::stdout();
+    let written = match args.value_of("format").unwrap_or("table") {
+        "table" => export::write_table(stdout.lock(), &results),
+        format_name => export::export_results(
+            stdout.lock(),
+            &results,
+            ExportFormat::from_name(format_name).expect("clap checks the format"),
+        ),
+    };
+    match written {
+        // the reader went away, e.g. piped into `head`
+        Err(ref error) if is_broken_pipe(error.as_ref()) => Ok(()),
         Err(error) => {
-            println!("{:?}", error);
-            println!("source: {:?}", error.source());
+            eprintln!("Can't write results: {}", error);
+            std::process::exit(1);
         }
-        Ok(results) => {
-            for typing_result in results.results {
-                println!("{}", typing_result);
+        Ok(()) => Ok(()),
+    }
+}
+
+// For scripts, a status of 1 and the reason on stderr if they can't be read
+fn query_results(config: &config::Config, query: &storage::ResultsQuery) -> Vec<wpm::TypingResult> {
+    match storage::open_backend(config).and_then(|mut backend| backend.query(query)) {
+        Err(error) => {
+            eprintln!("Can't read results: {}", error);
+            if let Some(source) = error.source() {
+                eprintln!("Caused by: {}", source);
+            }
+            if error.position().is_some() {
+                eprintln!("The results file is damaged, see `wpm verify` and `wpm repair`");
             }
+            std::process::exit(1);
+        }
+        Ok(results) => results,
+    }
+}
+
+fn print_stats(config: &config::Config, args: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let date_arg = |name| args.value_of(name).and_then(export::parse_date);
+    let query = storage::ResultsQuery {
+        since: date_arg("since"),
+        until: date_arg("until"),
+        tag: args.value_of("tag").map(String::from),
+        ..storage::ResultsQuery::default()
+    };
+    let mut results = query_results(config, &query);
+    results.sort_by_key(|typing_result| typing_result.timestamp_ms());
+    if args.is_present("keys") || args.is_present("ngrams") {
+        if args.is_present("keys") {
+            print_key_stats(&results);
         }
+        if args.is_present("ngrams") {
+            print_ngram_stats(&results);
+        }
+        return Ok(());
+    }
+    let summary = match stats::Summary::of(&results) {
+        Some(summary) => summary,
+        None => {
+            println!("No results yet");
+            return Ok(());
+        }
+    };
+    println!("Tests:              {}", summary.count);
+    println!("Mean WPM:           {:.1}", summary.mean_wpm);
+    println!("Median WPM:         {:.1}", summary.median_wpm);
+    println!("Best WPM:           {}", summary.best_wpm);
+    println!("Standard deviation: {:.1}", summary.std_dev_wpm);
+    println!("Accuracy:           {:.1}%", summary.accuracy * 100.0);
+
+    let last = args
+        .value_of("last")
+        .and_then(|last| last.parse().ok())
+        .unwrap_or(10);
+    if let Some(trend) = stats::trend(&results, last) {
+        let label = format!("Last {} tests:", trend.count);
+        print!("{:20}{:.1} wpm", label, trend.mean_wpm);
+        match trend.change() {
+            Some(change) => println!(" ({:+.1} on the {} before)", change, last),
+            None => println!(),
+        }
+    }
+
+    println!();
+    println!("Weekly averages:");
+    for week in stats::weekly_averages(&results) {
+        println!(
+            "  {}-W{:02}  {:3} tests  {:5.1} wpm",
+            week.year, week.week, week.count, week.mean_wpm
+        );
+    }
+
+    println!();
+    println!("Personal bests:");
+    for best in stats::personal_bests(&results) {
+        let length = best
+            .duration_secs
+            .map(|secs| format!("{}s", secs))
+            .unwrap_or_else(|| String::from("unknown"));
+        let date = best
+            .typing_result
+            .datetime()
+            .map(|datetime| datetime.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        println!("  {:8} {:3} wpm  {}", length, best.typing_result.wpm, date);
     }
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = clap::App::new("wpm")
-        .subcommand(clap::SubCommand::with_name("results"))
-        .get_matches();
+// Weakest first: the most errors, then the slowest
+fn by_weakness(a: &stats::KeyStats, b: &stats::KeyStats) -> std::cmp::Ordering {
+    let weakness = |key_stats: &stats::KeyStats| {
+        (
+            key_stats.error_rate(),
+            key_stats.mean_latency_ms.unwrap_or(0.0),
+        )
+    };
+    weakness(b)
+        .partial_cmp(&weakness(a))
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+fn key_stats_columns(key_stats: &stats::KeyStats) -> String {
+    let latency = key_stats
+        .mean_latency_ms
+        .map(|latency| format!("{:.0} ms", latency))
+        .unwrap_or_else(|| String::from("-"));
+    format!(
+        "{:>7}  {:>6}  {:>6.1}%  {:>7}",
+        key_stats.presses,
+        key_stats.errors,
+        key_stats.error_rate() * 100.0,
+        latency
+    )
+}
 
-    if let Some(_) = args.subcommand_matches("results") {
-        print_results()
+fn print_key_stats(results: &[wpm::TypingResult]) {
+    let mut keys = stats::key_stats(results).into_iter().collect::<Vec<_>>();
+    if keys.is_empty() {
+        println!("No keystrokes recorded yet");
+        return;
+    }
+    keys.sort_by(|(_, a), (_, b)| by_weakness(a, b));
+    println!("Key    Finger        Presses  Errors  Error %  Latency");
+    for (key, key_stats) in &keys {
+        let name = if *key == ' ' {
+            String::from("space")
+        } else {
+            key.to_string()
+        };
+        let finger = keyboard::finger(*key).map_or("", keyboard::Finger::name);
+        println!("{:6} {:12} {}", name, finger, key_stats_columns(key_stats));
+    }
+
+    let mut fingers = stats::finger_stats(results).into_iter().collect::<Vec<_>>();
+    fingers.sort_by(|(_, a), (_, b)| by_weakness(a, b));
+    println!();
+    println!("Finger              Presses  Errors  Error %  Latency");
+    for (finger, finger_stats) in &fingers {
+        println!("{:19} {}", finger.name(), key_stats_columns(finger_stats));
+    }
+}
+
+fn print_ngram_stats(results: &[wpm::TypingResult]) {
+    const NGRAMS_SHOWN: usize = 10;
+    let print_ngrams = |heading: &str, ngrams: &[&stats::NgramStats]| {
+        println!("{}:", heading);
+        for ngram in ngrams {
+            let latency = ngram
+                .mean_latency_ms
+                .map(|latency| format!("{:.0} ms", latency))
+                .unwrap_or_else(|| String::from("-"));
+            println!(
+                "  {:5} {:5} times  {:>7}  {:5.1}% errors",
+                ngram.ngram,
+                ngram.count,
+                latency,
+                ngram.error_rate() * 100.0
+            );
+        }
+    };
+    for (n, name) in [(2, "bigrams"), (3, "trigrams")].iter() {
+        let ngrams = stats::ngram_stats(results, *n);
+        if ngrams.is_empty() {
+            println!("No {} recorded yet", name);
+            continue;
+        }
+        print_ngrams(
+            &format!("Slowest {}", name),
+            &stats::slowest_ngrams(&ngrams, NGRAMS_SHOWN),
+        );
+        println!();
+        print_ngrams(
+            &format!("Most error prone {}", name),
+            &stats::most_error_prone_ngrams(&ngrams, NGRAMS_SHOWN),
+        );
+        println!();
+    }
+}
+
+fn is_broken_pipe(error: &(dyn Error + 'static)) -> bool {
+    let mut error = Some(error);
+    while let Some(cause) = error {
+        if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+            return io_error.kind() == io::ErrorKind::BrokenPipe;
+        }
+        error = cause.source();
+    }
+    false
+}
+
+fn validate_date(date: String) -> Result<(), String> {
+    export::parse_date(&date)
+        .map(|_| ())
+        .ok_or_else(|| String::from("expected a date like 2019-04-25 or 2019-04-25T20:14:19+01:00"))
+}
+
+fn validate_number(number: String) -> Result<(), String> {
+    number
+        .parse::<u32>()
+        .map(|_| ())
+        .map_err(|_| String::from("expected a whole number"))
+}
+
+fn export_results(config: &config::Config, args: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let format = args
+        .value_of("format")
+        .and_then(ExportFormat::from_name)
+        .ok_or("Unknown export format, expected csv or json")?;
+    let mut results = storage::open_backend(config)?.list()?;
+    results.sort_by_key(|typing_result| typing_result.timestamp_ms());
+    match args.value_of("output") {
+        Some(output) => export::export_results(File::create(output)?, &results, format),
+        None => export::export_results(io::stdout(), &results, format),
+    }
+}
+
+fn import_results(config: &config::Config, args: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(args.value_of("FILE").expect("FILE is required"));
+    let format = match args.value_of("format") {
+        Some(format_name) => ExportFormat::from_name(format_name),
+        None => ExportFormat::from_path(path),
+    }
+    .ok_or("Unknown import format, use a .csv or .json file or pass --format")?;
+
+    let imported = export::import_results(File::open(path)?, format)?;
+    let mut backend = storage::open_backend(config)?;
+    let (new_results, duplicates) = wpm::unseen_results(&backend.list()?, imported);
+    for typing_result in &new_results {
+        backend.append(typing_result)?;
+    }
+    println!(
+        "Imported {} results, skipped {} duplicates",
+        new_results.len(),
+        duplicates
+    );
+    Ok(())
+}
+
+fn merge_results(config: &config::Config, args: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let paths: Vec<PathBuf> = args
+        .values_of("FILE")
+        .expect("FILE is required")
+        .map(PathBuf::from)
+        .collect();
+    let mut backend: Box<dyn storage::ResultsBackend> = match args.value_of("output") {
+        Some(output) => Box::new(storage::FileBackend::new(PathBuf::from(output))),
+        None => storage::open_backend(config)?,
+    };
+    let summary = storage::merge_results_files(backend.as_mut(), &paths)?;
+    // upgrades any old records still in the combined file
+    backend.compact()?;
+    println!(
+        "Merged {} results, skipped {} duplicates",
+        summary.added, summary.duplicates
+    );
+    Ok(())
+}
+
+fn backup_results(config: &config::Config, args: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = file_results_path(config)?;
+    if args.is_present("list") {
+        for backup in storage::list_backups(&path)? {
+            println!("{}", backup.display());
+        }
     } else {
-        run_gui()
+        let backup = storage::backup_results(&path, config.backups_to_keep)?;
+        println!("Backed up results to {}", backup.display());
+    }
+    Ok(())
+}
+
+fn restore_results(config: &config::Config, args: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = file_results_path(config)?;
+    let backup = match args.value_of("BACKUP") {
+        Some(backup) => PathBuf::from(backup),
+        None => storage::last_good_backup(&path)?.ok_or("No readable backups to restore")?,
+    };
+    storage::restore_backup(&path, &backup)?;
+    println!("Restored results from {}", backup.display());
+    Ok(())
+}
+
+fn verify_results(config: &config::Config) -> Result<(), Box<dyn Error>> {
+    let path = file_results_path(config)?;
+    let report = storage::verify_results_file(&path)?;
+    println!("{}", path.display());
+    for (version, count) in &report.records_by_version {
+        let note = if *version > storage::CURRENT_VERSION {
+            " (from a newer wpm, skipped)"
+        } else {
+            ""
+        };
+        println!("  version {} records: {}{}", version, count, note);
+    }
+    println!("  edit records: {}", report.edit_records);
+    println!(
+        "  records needing upgrade: {}",
+        report.records_needing_upgrade
+    );
+    println!("  readable results: {}", report.readable.results.len());
+    for region in &report.damaged_regions {
+        println!(
+            "  damaged bytes {}..{}: {}",
+            region.start, region.end, region.error
+        );
+    }
+    if report.is_damaged() {
+        eprintln!("The results file is damaged, `wpm repair` keeps what can be read");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn repair_results(config: &config::Config) -> Result<(), Box<dyn Error>> {
+    let path = file_results_path(config)?;
+    let (report, damaged_copy) = storage::repair_results_file(&path)?;
+    println!(
+        "Rewrote {} results to {}",
+        report.readable.results.len(),
+        path.display()
+    );
+    if let Some(damaged_copy) = damaged_copy {
+        println!(
+            "Dropped {} damaged regions, the original is kept at {}",
+            report.damaged_regions.len(),
+            damaged_copy.display()
+        );
+    }
+    Ok(())
+}
+
+// Backups are copies of the results file, which the SQLite backend doesn't use
+fn file_results_path(config: &config::Config) -> Result<PathBuf, Box<dyn Error>> {
+    if config.storage_backend != StorageBackend::File {
+        return Err("Backups are only kept for the file storage backend".into());
+    }
+    Ok(storage::results_path(config)?)
+}
+
+// An encrypted results file needs its passphrase, from WPM_PASSPHRASE or asked for
+fn unlock_results_file(config: &config::Config) -> Result<(), Box<dyn Error>> {
+    if config.storage_backend != StorageBackend::File {
+        return Ok(());
+    }
+    let path = storage::results_path(config)?;
+    let passphrase = match env::var("WPM_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) if storage::is_encrypted_file(&path)? => {
+            rpassword::prompt_password("Passphrase for your typing results: ")?
+        }
+        Err(_) => return Ok(()),
+    };
+    storage::set_passphrase(&path, Some(passphrase));
+    Ok(())
+}
+
+fn change_passphrase(
+    config: &config::Config,
+    args: &clap::ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let path = file_results_path(config)?;
+    let new_passphrase = if args.is_present("remove") {
+        None
+    } else {
+        let passphrase = rpassword::prompt_password("New passphrase: ")?;
+        if passphrase.is_empty() {
+            return Err("The passphrase can't be empty, use --remove to stop encrypting".into());
+        }
+        if passphrase != rpassword::prompt_password("New passphrase again: ")? {
+            return Err("The passphrases don't match".into());
+        }
+        Some(passphrase)
+    };
+    let encrypted = new_passphrase.is_some();
+    storage::change_passphrase(&path, new_passphrase)?;
+    if encrypted {
+        println!("Results are encrypted with the new passphrase");
+    } else {
+        println!("Results are no longer encrypted");
+    }
+    Ok(())
+}
+
+// A missing or wrong passphrase isn't damage, and restoring a backup (maybe
+// an older unencrypted one) over the results would lose newer ones
+fn ask_for_passphrase_until_readable(path: &Path) -> Result<(), Box<dyn Error>> {
+    const ATTEMPTS: usize = 3;
+    for _ in 0..ATTEMPTS {
+        match storage::read_results_from_path(path) {
+            Err(error @ storage::StorageError::NoPassphrase)
+            | Err(error @ storage::StorageError::WrongPassphrase) => {
+                eprintln!("Can't read results from {}: {}", path.display(), error);
+                if !io::stdin().is_terminal() {
+                    return Err("Set WPM_PASSPHRASE to the passphrase for the results".into());
+                }
+                let passphrase =
+                    rpassword::prompt_password("Passphrase for your typing results: ")?;
+                storage::set_passphrase(path, Some(passphrase));
+            }
+            _ => return Ok(()),
+        }
+    }
+    match storage::read_results_from_path(path) {
+        Err(storage::StorageError::NoPassphrase) | Err(storage::StorageError::WrongPassphrase) => {
+            Err("Wrong passphrase for the results".into())
+        }
+        _ => Ok(()),
+    }
+}
+
+// Offers to put back the last good backup if the results file can't be read,
+// otherwise takes the day's backup and compacts it. Only run before taking a test, messages
+// go to stderr and there's only a prompt when someone is there to answer it.
+fn check_results_file(config: &config::Config) -> Result<(), Box<dyn Error>> {
+    if config.storage_backend != StorageBackend::File {
+        return Ok(());
+    }
+    let path = storage::results_path(config)?;
+    ask_for_passphrase_until_readable(&path)?;
+    if let Err(error) = storage::read_results_from_path(&path) {
+        eprintln!("Can't read results from {}: {}", path.display(), error);
+        let backup = match storage::last_good_backup(&path)? {
+            Some(backup) => backup,
+            None => {
+                eprintln!("There's no readable backup to restore");
+                return Ok(());
+            }
+        };
+        if !io::stdin().is_terminal() {
+            eprintln!(
+                "`wpm restore {}` puts back the last good backup",
+                backup.display()
+            );
+            return Ok(());
+        }
+        eprint!("Restore the backup {}? [y/N] ", backup.display());
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if answer.trim().eq_ignore_ascii_case("y") {
+            storage::restore_backup(&path, &backup)?;
+            eprintln!("Restored results from {}", backup.display());
+        }
+        return Ok(());
+    }
+    storage::daily_backup(&path, config.backups_to_keep)?;
+    storage::open_backend(config)?.compact()?;
+    Ok(())
+}
+
+fn report_check_errors(config: &config::Config) {
+    if let Err(error) = check_results_file(config) {
+        eprintln!("Error checking the results file: {}", error);
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let format_arg = clap::Arg::with_name("format")
+        .long("format")
+        .value_name("FORMAT")
+        .possible_values(&["csv", "json"])
+        .takes_value(true);
+    let args =
+        clap::App::new("wpm")
+            .arg(
+                clap::Arg::with_name("results-file")
+                    .long("results-file")
+                    .value_name("FILE")
+                    .help("Where to keep typing results (overrides WPM_RESULTS_FILE)")
+                    .takes_value(true)
+                    .global(true),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("results")
+                    .about("Prints the stored results, oldest first")
+                    .arg(
+                        clap::Arg::with_name("since")
+                            .long("since")
+                            .value_name("DATE")
+                            .validator(validate_date)
+                            .help("Only results from this date on"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("until")
+                            .long("until")
+                            .value_name("DATE")
+                            .validator(validate_date)
+                            .help("Only results from before this date"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("min-wpm")
+                            .long("min-wpm")
+                            .value_name("WPM")
+                            .validator(validate_number)
+                            .help("Only results at least this fast"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("limit")
+                            .long("limit")
+                            .value_name("COUNT")
+                            .validator(validate_number)
+                            .help("At most this many, the newest or, sorting by WPM, the fastest"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("sort")
+                            .long("sort")
+                            .possible_values(&["date", "wpm"])
+                            .default_value("date")
+                            .help("By date oldest first, or by WPM fastest first"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("tag")
+                            .long("tag")
+                            .takes_value(true)
+                            .help("Only results with this tag"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("format")
+                            .long("format")
+                            .possible_values(&["table", "csv", "json"])
+                            .default_value("table"),
+                    ),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("stats")
+                    .about("Summarises the stored results")
+                    .arg(
+                        clap::Arg::with_name("since")
+                            .long("since")
+                            .value_name("DATE")
+                            .validator(validate_date)
+                            .help("Only results from this date on"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("until")
+                            .long("until")
+                            .value_name("DATE")
+                            .validator(validate_date)
+                            .help("Only results from before this date"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("tag")
+                            .long("tag")
+                            .takes_value(true)
+                            .help("Only results with this tag"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("last")
+                            .long("last")
+                            .value_name("COUNT")
+                            .validator(validate_number)
+                            .default_value("10")
+                            .help("How many of the latest tests the trend is over"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("keys")
+                            .long("keys")
+                            .help("Error rates and latencies for each key and finger instead"),
+                    )
+                    .arg(clap::Arg::with_name("ngrams").long("ngrams").help(
+                        "The slowest and most error prone pairs and triples of letters instead",
+                    )),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("tui")
+                    .about("Takes a typing test in the terminal instead of a window")
+                    .arg(clap::Arg::with_name("practice").long("practice").help(
+                        "Picks words with the keys, letter pairs and words you get wrong or type \
+                         slowly most",
+                    )),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("export")
+                    .about("Writes all results out as CSV or JSON")
+                    .arg(format_arg.clone().default_value("csv"))
+                    .arg(
+                        clap::Arg::with_name("output")
+                            .long("output")
+                            .short("o")
+                            .value_name("FILE")
+                            .help("Write to a file instead of stdout")
+                            .takes_value(true),
+                    ),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("import")
+                    .about("Adds results from a CSV or JSON export, skipping ones we already have")
+                    .arg(format_arg.help("Defaults to the file's extension"))
+                    .arg(clap::Arg::with_name("FILE").required(true)),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("merge")
+                    .about("Combines other results files with ours, skipping duplicates")
+                    .arg(
+                        clap::Arg::with_name("output")
+                            .long("output")
+                            .short("o")
+                            .value_name("FILE")
+                            .help("Merge into this results file instead of ours")
+                            .takes_value(true),
+                    )
+                    .arg(clap::Arg::with_name("FILE").required(true).multiple(true)),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("backup")
+                    .about("Backs up the results file")
+                    .arg(
+                        clap::Arg::with_name("list")
+                            .long("list")
+                            .help("List the backups instead, newest first"),
+                    ),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("restore")
+                    .about("Replaces the results file with a backup")
+                    .arg(
+                        clap::Arg::with_name("BACKUP")
+                            .help("Defaults to the newest backup that can be read"),
+                    ),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("verify")
+                    .about("Checks the results file for damaged or old records"),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("repair")
+                    .about("Rewrites the results file with every record that can be read"),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("passphrase")
+                    .about("Encrypts the results file with a new passphrase")
+                    .arg(
+                        clap::Arg::with_name("remove")
+                            .long("remove")
+                            .help("Decrypt the results file and stop encrypting it"),
+                    ),
+            )
+            .get_matches();
+
+    let mut config = config::Config::new();
+    if let Some(results_file) = args.value_of("results-file") {
+        config.results_path = Some(PathBuf::from(results_file));
+    }
+    if let Err(error) = storage::migrate_from_config_dir(&config) {
+        eprintln!("Error moving results to the data directory: {}", error);
+    }
+
+    if let Err(error) = unlock_results_file(&config) {
+        eprintln!("Error unlocking the results file: {}", error);
+    }
+
+    match args.subcommand() {
+        ("passphrase", Some(passphrase_args)) => {
+            return change_passphrase(&config, passphrase_args)
+        }
+        ("backup", Some(backup_args)) => return backup_results(&config, backup_args),
+        ("restore", Some(restore_args)) => return restore_results(&config, restore_args),
+        ("verify", Some(_)) => return verify_results(&config),
+        ("repair", Some(_)) => return repair_results(&config),
+        _ => {}
+    }
+    match args.subcommand() {
+        ("results", Some(results_args)) => print_results(&config, results_args),
+        ("stats", Some(stats_args)) => print_stats(&config, stats_args),
+        ("tui", Some(args)) => {
+            report_check_errors(&config);
+            wpm::tui::run(&config, args.is_present("practice"))
+        }
+        ("export", Some(export_args)) => export_results(&config, export_args),
+        ("import", Some(import_args)) => import_results(&config, import_args),
+        ("merge", Some(merge_args)) => merge_results(&config, merge_args),
+        _ => {
+            report_check_errors(&config);
+            run_gui(config)
+        }
     }
 }
@@ -0,0 +1,277 @@
+use crate::stats::{self, KeyStats, NgramStats};
+use crate::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::BTreeMap;
+
+// How much more often a word comes up for each unit of weakness in its keys
+// and bigrams, and for each time it was typed wrong
+const KEY_WEIGHT: f64 = 4.0;
+const BIGRAM_WEIGHT: f64 = 4.0;
+const MISSED_WORD_WEIGHT: f64 = 2.0;
+// so a word missed many times doesn't crowd out everything else
+const MAX_MISSED_COUNT: usize = 5;
+
+// How far above average something's error rate and latency are, 0 if it's
+// no worse than average in either
+fn weakness(error_rate: f64, latency_ms: Option<f64>, average: &(f64, Option<f64>)) -> f64 {
+    let above = |value: f64, average: f64| {
+        if average > 0.0 {
+            (value / average - 1.0).max(0.0)
+        } else {
+            0.0
+        }
+    };
+    let latency_weakness = match (latency_ms, average.1) {
+        (Some(latency_ms), Some(average_ms)) => above(latency_ms, average_ms),
+        _ => 0.0,
+    };
+    above(error_rate, average.0) + latency_weakness
+}
+
+// Mean error rate and latency, weighted by how often each was typed
+fn average(stats: impl Iterator<Item = (usize, f64, Option<f64>)> + Clone) -> (f64, Option<f64>) {
+    let (count, errors) = stats
+        .clone()
+        .fold((0, 0.0), |(count, errors), (n, error_rate, _)| {
+            (count + n, errors + error_rate * n as f64)
+        });
+    let (timed, latency) =
+        stats.fold(
+            (0, 0.0),
+            |(timed, total), (n, _, latency_ms)| match latency_ms {
+                Some(latency_ms) => (timed + n, total + latency_ms * n as f64),
+                None => (timed, total),
+            },
+        );
+    (
+        if count > 0 {
+            errors / count as f64
+        } else {
+            0.0
+        },
+        if timed > 0 {
+            Some(latency / timed as f64)
+        } else {
+            None
+        },
+    )
+}
+
+// What the stored results say needs practice
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Weaknesses {
+    pub keys: BTreeMap<char, f64>,
+    pub bigrams: BTreeMap<String, f64>,
+    pub missed_words: BTreeMap<String, usize>,
+}
+
+impl Weaknesses {
+    pub fn from_results(results: &[TypingResult]) -> Self {
+        let key_stats = stats::key_stats(results);
+        let key_average = average(
+            key_stats
+                .values()
+                .map(|key: &KeyStats| (key.presses, key.error_rate(), key.mean_latency_ms)),
+        );
+        let keys = key_stats
+            .iter()
+            .map(|(&key, key_stats)| {
+                let weakness = weakness(
+                    key_stats.error_rate(),
+                    key_stats.mean_latency_ms,
+                    &key_average,
+                );
+                (key, weakness)
+            })
+            .filter(|(_, weakness)| *weakness > 0.0)
+            .collect();
+
+        // Bigrams seen only once or twice say more about the moment than the typist
+        let bigram_stats = stats::ngram_stats(results, 2)
+            .into_iter()
+            .filter(|bigram| bigram.count >= stats::MIN_NGRAM_COUNT)
+            .collect::<Vec<_>>();
+        let bigram_average = average(bigram_stats.iter().map(|bigram: &NgramStats| {
+            (bigram.count, bigram.error_rate(), bigram.mean_latency_ms)
+        }));
+        let bigrams = bigram_stats
+            .iter()
+            .map(|bigram| {
+                let weakness =
+                    weakness(bigram.error_rate(), bigram.mean_latency_ms, &bigram_average);
+                (bigram.ngram.clone(), weakness)
+            })
+            .filter(|(_, weakness)| *weakness > 0.0)
+            .collect();
+
+        let mut missed_words = BTreeMap::new();
+        for word in results
+            .iter()
+            .flat_map(|typing_result| &typing_result.missed_words)
+        {
+            *missed_words.entry(word.to_lowercase()).or_insert(0) += 1;
+        }
+
+        Self {
+            keys,
+            bigrams,
+            missed_words,
+        }
+    }
+
+    // 1 for a word with nothing to practice, more the weaker its keys and
+    // bigrams are and the more often it was missed
+    pub fn word_weight(&self, word: &str) -> f64 {
+        let letters = word
+            .chars()
+            .flat_map(char::to_lowercase)
+            .collect::<Vec<_>>();
+        if letters.is_empty() {
+            return 1.0;
+        }
+        let key_weakness = letters
+            .iter()
+            .map(|&letter| {
+                self.keys
+                    .get(&keyboard::key_for(letter))
+                    .cloned()
+                    .unwrap_or(0.0)
+            })
+            .sum::<f64>()
+            / letters.len() as f64;
+        let bigram_weakness = if letters.len() < 2 {
+            0.0
+        } else {
+            letters
+                .windows(2)
+                .map(|pair| {
+                    self.bigrams
+                        .get(&pair.iter().collect::<String>())
+                        .cloned()
+                        .unwrap_or(0.0)
+                })
+                .sum::<f64>()
+                / (letters.len() - 1) as f64
+        };
+        let missed = self
+            .missed_words
+            .get(&word.to_lowercase())
+            .cloned()
+            .unwrap_or(0)
+            .min(MAX_MISSED_COUNT);
+        1.0 + KEY_WEIGHT * key_weakness
+            + BIGRAM_WEIGHT * bigram_weakness
+            + MISSED_WORD_WEIGHT * missed as f64
+    }
+
+    // Words worth practising, the top 200 and every word that was missed
+    pub fn candidates(&self) -> Vec<String> {
+        let mut candidates = words::top_200::words();
+        for word in self.missed_words.keys() {
+            if !candidates.contains(word) {
+                candidates.push(word.clone());
+            }
+        }
+        candidates
+    }
+}
+
+// `count` words picked from `candidates` in proportion to their weight, never
+// the same word twice in a row
+pub fn practice_words<R: Rng>(
+    weaknesses: &Weaknesses,
+    candidates: &[String],
+    count: usize,
+    rng: &mut R,
+) -> Vec<String> {
+    let weighted = candidates
+        .iter()
+        .map(|word| (word, weaknesses.word_weight(word)))
+        .collect::<Vec<_>>();
+    let mut words: Vec<String> = Vec::with_capacity(count);
+    while words.len() < count {
+        let last = words.last();
+        let choices = weighted
+            .iter()
+            .filter(|(word, _)| candidates.len() < 2 || Some(*word) != last)
+            .collect::<Vec<_>>();
+        match choices.choose_weighted(rng, |(_, weight)| *weight) {
+            Ok((word, _)) => words.push((*word).clone()),
+            Err(_) => break,
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+fn keystroke(expected: char, typed: char, interval_ms: u32) -> Keystroke {
+    Keystroke {
+        expected: Some(expected),
+        typed,
+        interval_ms,
+    }
+}
+
+#[test]
+fn test_weak_keys_bigrams_and_missed_words_weigh_more() {
+    // "q" is typed slowly and "x" wrong, everything else well
+    let mut keystrokes = Vec::new();
+    for _ in 0..4 {
+        for (expected, typed, interval_ms) in &[
+            ('a', 'a', 100),
+            ('s', 's', 100),
+            ('q', 'q', 400),
+            (' ', ' ', 100),
+            ('d', 'd', 100),
+            ('x', 'c', 100),
+            (' ', ' ', 100),
+        ] {
+            keystrokes.push(keystroke(*expected, *typed, *interval_ms));
+        }
+    }
+    let results = vec![TypingResult {
+        keystrokes,
+        missed_words: vec![String::from("Dx"), String::from("dx")],
+        ..TypingResult::default()
+    }];
+    let weaknesses = Weaknesses::from_results(&results);
+
+    assert!(weaknesses.keys[&'q'] > 0.0);
+    assert!(weaknesses.keys[&'x'] > 0.0);
+    assert!(!weaknesses.keys.contains_key(&'a'));
+    assert!(weaknesses.bigrams[&String::from("sq")] > 0.0);
+    assert_eq!(Some(&2), weaknesses.missed_words.get("dx"));
+
+    assert_eq!(1.0, weaknesses.word_weight("sad"));
+    assert!(weaknesses.word_weight("quiz") > weaknesses.word_weight("sad"));
+    assert!(weaknesses.word_weight("squad") > weaknesses.word_weight("quad"));
+    assert!(weaknesses.word_weight("dx") > weaknesses.word_weight("xd"));
+    assert!(weaknesses.candidates().contains(&String::from("dx")));
+}
+
+#[test]
+fn test_practice_words_favour_the_weak_ones() {
+    use rand::SeedableRng;
+
+    let weaknesses = Weaknesses {
+        missed_words: vec![(String::from("weak"), MAX_MISSED_COUNT)]
+            .into_iter()
+            .collect(),
+        ..Weaknesses::default()
+    };
+    let candidates = vec![
+        String::from("weak"),
+        String::from("fine"),
+        String::from("good"),
+    ];
+    let mut rng = rand::rngs::StdRng::seed_from_u64(50);
+    let words = practice_words(&weaknesses, &candidates, 300, &mut rng);
+
+    assert_eq!(300, words.len());
+    assert!(words.windows(2).all(|pair| pair[0] != pair[1]));
+    let count = |word: &str| words.iter().filter(|w| *w == word).count();
+    assert!(count("weak") > count("fine"));
+    assert!(count("weak") > count("good"));
+    assert!(count("fine") > 0);
+}
@@ -20,6 +20,10 @@ pub trait Screen {
         false
     }
     fn mouse_click(&mut self, _position: Vector2<f32>) {}
+    // Whether quitting now would lose something, so it should be confirmed
+    fn has_unsaved_work(&self) -> bool {
+        false
+    }
     fn update(
         &mut self,
         dt: f32,
@@ -1,9 +1,13 @@
+mod keyboard_screen;
 mod menu;
 mod results_list_screen;
 mod results_screen;
+mod stats_screen;
 mod test_screen;
 
+pub use keyboard_screen::KeyboardScreen;
 pub use menu::Menu;
 pub use results_list_screen::ResultsListScreen;
 pub use results_screen::ResultsScreen;
+pub use stats_screen::StatsScreen;
 pub use test_screen::TestScreen;
@@ -0,0 +1,360 @@
+use crate::keyboard;
+use crate::layout::ElementLayout;
+use crate::screens;
+use crate::*;
+use cgmath::*;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+const TITLE_FONT_SIZE: f32 = 48.0;
+const TAB_FONT_SIZE: f32 = 32.0;
+const KEY_FONT_SIZE: f32 = 24.0;
+const WEAKEST_KEYS: usize = 5;
+
+// In key widths. The rows after the number row start further in, and the
+// widest is 15 keys across counting backspace.
+const ROW_OFFSETS: [f32; 4] = [0.0, 1.5, 1.75, 2.25];
+const KEYBOARD_WIDTH: f32 = 15.0;
+const SPACE_BAR_OFFSET: f32 = 4.0;
+const SPACE_BAR_WIDTH: f32 = 6.0;
+const MAX_KEY_SIZE: f32 = 80.0;
+const KEY_GAP: f32 = 4.0;
+
+const UNTYPED_KEY_COLOR: ColorArray = BASE1;
+
+// From the best key to the worst
+fn heat_color(fraction: f64) -> ColorArray {
+    let fraction = fraction.clamp(0.0, 1.0) as f32;
+    let (from, to, amount) = if fraction < 0.5 {
+        (GREEN, YELLOW, fraction * 2.0)
+    } else {
+        (YELLOW, RED, fraction * 2.0 - 1.0)
+    };
+    let mut color = from;
+    for (channel, to) in color.iter_mut().zip(to.iter()) {
+        *channel += (to - *channel) * amount;
+    }
+    color
+}
+
+fn key_name(key: char) -> String {
+    if key == ' ' {
+        String::from("space")
+    } else {
+        key.to_string()
+    }
+}
+
+// A summary of the keys and the finger that did worst by `weakness`
+fn weakest_text<F>(
+    key_stats: &BTreeMap<char, stats::KeyStats>,
+    finger_stats: &BTreeMap<keyboard::Finger, stats::KeyStats>,
+    weakness: F,
+) -> String
+where
+    F: Fn(&stats::KeyStats) -> Option<(f64, String)>,
+{
+    let by_weakness = |a: &(f64, String), b: &(f64, String)| {
+        b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal)
+    };
+    let mut keys = key_stats
+        .iter()
+        .filter_map(|(&key, key_stats)| {
+            let (value, text) = weakness(key_stats)?;
+            Some((value, format!("{} {}", key_name(key), text)))
+        })
+        .collect::<Vec<_>>();
+    if keys.is_empty() {
+        return String::from("No keystrokes recorded yet");
+    }
+    keys.sort_by(by_weakness);
+    let mut fingers = finger_stats
+        .iter()
+        .filter_map(|(finger, finger_stats)| {
+            weakness(finger_stats).map(|(value, _)| (value, String::from(finger.name())))
+        })
+        .collect::<Vec<_>>();
+    fingers.sort_by(by_weakness);
+
+    let keys = keys
+        .into_iter()
+        .take(WEAKEST_KEYS)
+        .map(|(_, text)| text)
+        .collect::<Vec<_>>()
+        .join(", ");
+    match fingers.first() {
+        Some((_, finger)) => format!("Weakest keys: {}. Weakest finger: {}", keys, finger),
+        None => format!("Weakest keys: {}", keys),
+    }
+}
+
+struct HeatKey {
+    label: Label,
+    rect: Rect,
+    row: usize,
+    column: usize,
+    error_rate: Option<f64>, // None if it hasn't been typed
+    latency_ms: Option<f64>,
+}
+
+// Every stored keystroke on a keyboard, coloured by how often each key was
+// typed wrong or how slow it was
+pub struct KeyboardScreen {
+    need_font_recalc: bool,
+    back_label: Label,
+    title: Label,
+    errors_tab: Label,
+    latency_tab: Label,
+    showing_latency: bool,
+    errors_summary: Label,
+    latency_summary: Label,
+    keys: Vec<HeatKey>,
+    max_error_rate: f64,
+    latency_range: (f64, f64),
+}
+
+impl KeyboardScreen {
+    pub fn new(gfx_window: &mut GfxWindow, config: &Config) -> Self {
+        let results = match storage::open_backend(config).and_then(|mut backend| backend.list()) {
+            Ok(results) => results,
+            Err(err) => {
+                println!("Error reading results: {:?}", err);
+                Vec::new()
+            }
+        };
+        let key_stats = stats::key_stats(&results);
+        let finger_stats = stats::finger_stats(&results);
+
+        let rows = keyboard::KEY_ROWS
+            .iter()
+            .map(|row| row.chars().collect::<Vec<_>>())
+            .chain(std::iter::once(vec![' ']));
+        let mut keys = Vec::new();
+        for (row, row_keys) in rows.enumerate() {
+            for (column, key) in row_keys.into_iter().enumerate() {
+                let key_stats = key_stats.get(&key);
+                keys.push(HeatKey {
+                    label: Label::new(
+                        KEY_FONT_SIZE,
+                        gfx_window.fonts.iosevka_font_id,
+                        TEXT_COLOR,
+                        key_name(key),
+                        gfx_window,
+                    ),
+                    rect: Rect::default(),
+                    row,
+                    column,
+                    error_rate: key_stats.map(stats::KeyStats::error_rate),
+                    latency_ms: key_stats.and_then(|key_stats| key_stats.mean_latency_ms),
+                });
+            }
+        }
+        let max_error_rate = keys
+            .iter()
+            .filter_map(|key| key.error_rate)
+            .fold(0.0, f64::max);
+        let latencies = keys.iter().filter_map(|key| key.latency_ms);
+        let latency_range = (
+            latencies.clone().fold(f64::INFINITY, f64::min),
+            latencies.fold(0.0, f64::max),
+        );
+
+        let errors_summary = weakest_text(&key_stats, &finger_stats, |key_stats| {
+            let error_rate = key_stats.error_rate();
+            Some((error_rate, format!("{:.1}%", error_rate * 100.0)))
+        });
+        let latency_summary = weakest_text(&key_stats, &finger_stats, |key_stats| {
+            key_stats
+                .mean_latency_ms
+                .map(|latency| (latency, format!("{:.0} ms", latency)))
+        });
+        let text_label = |font_size, text: &str, gfx_window: &mut GfxWindow| {
+            Label::new(
+                font_size,
+                gfx_window.fonts.roboto_font_id,
+                TEXT_COLOR,
+                String::from(text),
+                gfx_window,
+            )
+        };
+
+        Self {
+            need_font_recalc: true,
+            back_label: gfx_window.back_label(),
+            title: text_label(TITLE_FONT_SIZE, "Weak keys", gfx_window),
+            errors_tab: text_label(TAB_FONT_SIZE, "Errors", gfx_window),
+            latency_tab: text_label(TAB_FONT_SIZE, "Speed", gfx_window),
+            showing_latency: false,
+            errors_summary: text_label(TAB_FONT_SIZE, &errors_summary, gfx_window),
+            latency_summary: text_label(TAB_FONT_SIZE, &latency_summary, gfx_window),
+            keys,
+            max_error_rate,
+            latency_range,
+        }
+    }
+
+    // Keys that haven't been typed stay grey
+    fn key_color(&self, key: &HeatKey) -> ColorArray {
+        let fraction = if self.showing_latency {
+            let (fastest, slowest) = self.latency_range;
+            key.latency_ms.map(|latency| {
+                if slowest > fastest {
+                    (latency - fastest) / (slowest - fastest)
+                } else {
+                    0.0
+                }
+            })
+        } else {
+            key.error_rate.map(|error_rate| {
+                if self.max_error_rate > 0.0 {
+                    error_rate / self.max_error_rate
+                } else {
+                    0.0
+                }
+            })
+        };
+        fraction.map_or(UNTYPED_KEY_COLOR, heat_color)
+    }
+
+    fn summary(&self) -> &Label {
+        if self.showing_latency {
+            &self.latency_summary
+        } else {
+            &self.errors_summary
+        }
+    }
+
+    fn update_font_metrics(&mut self, gfx_window: &mut GfxWindow) {
+        let top_padding = 30.0;
+        let side_padding = 40.0;
+        let label_gap = 10.0;
+        let window_dim = gfx_window.window_dim();
+
+        self.back_label.rect.position = vec2(15.0, top_padding);
+        ElementLayout::center_horizontally(
+            window_dim,
+            self.title.rect.bounds,
+            &mut self.title.rect.position,
+        );
+        self.title.rect.position.y = top_padding;
+
+        let tabs_top = top_padding + self.title.rect.bounds.y + label_gap * 2.0;
+        let tabs_width = self.errors_tab.rect.bounds.x + self.latency_tab.rect.bounds.x;
+        let tabs_left = (window_dim.x - tabs_width - label_gap * 4.0) / 2.0;
+        self.errors_tab.rect.position = vec2(tabs_left, tabs_top);
+        self.latency_tab.rect.position = vec2(
+            self.errors_tab.rect.right_edge() + label_gap * 4.0,
+            tabs_top,
+        );
+
+        let key_size = ((window_dim.x - side_padding * 2.0) / KEYBOARD_WIDTH).min(MAX_KEY_SIZE);
+        let keyboard_left = (window_dim.x - key_size * KEYBOARD_WIDTH) / 2.0;
+        let keyboard_top = tabs_top + self.errors_tab.rect.bounds.y + label_gap * 3.0;
+        for key in &mut self.keys {
+            let (offset, width) = if key.row < ROW_OFFSETS.len() {
+                (ROW_OFFSETS[key.row] + key.column as f32, 1.0)
+            } else {
+                (SPACE_BAR_OFFSET, SPACE_BAR_WIDTH)
+            };
+            key.rect = Rect::new(
+                vec2(
+                    keyboard_left + offset * key_size,
+                    keyboard_top + key.row as f32 * key_size,
+                ),
+                vec2(width * key_size - KEY_GAP, key_size - KEY_GAP),
+            );
+            key.label.rect.position = key.rect.center_point() - key.label.rect.bounds / 2.0;
+        }
+
+        let keyboard_bottom = keyboard_top + (ROW_OFFSETS.len() + 1) as f32 * key_size;
+        for summary in [&mut self.errors_summary, &mut self.latency_summary].iter_mut() {
+            ElementLayout::center_horizontally(
+                window_dim,
+                summary.rect.bounds,
+                &mut summary.rect.position,
+            );
+            summary.rect.position.y = keyboard_bottom + label_gap * 2.0;
+        }
+    }
+}
+
+impl Screen for KeyboardScreen {
+    fn maybe_change_to_screen(
+        &self,
+        gfx_window: &mut GfxWindow,
+        _config: &Config,
+    ) -> Option<Box<dyn Screen>> {
+        if self.back_label.ui_state.pressed {
+            Some(Box::new(screens::Menu::new(gfx_window)))
+        } else {
+            None
+        }
+    }
+
+    fn mouse_click(&mut self, position: Vector2<f32>) {
+        if self.back_label.rect.contains_point(position) {
+            self.back_label.ui_state.pressed = true;
+        } else if self.errors_tab.rect.contains_point(position) {
+            self.showing_latency = false;
+        } else if self.latency_tab.rect.contains_point(position) {
+            self.showing_latency = true;
+        }
+    }
+
+    fn update(
+        &mut self,
+        _dt: f32,
+        _mouse_position: Vector2<f32>,
+        _config: &Config,
+        gfx_window: &mut GfxWindow,
+    ) -> bool {
+        if self.need_font_recalc {
+            self.update_font_metrics(gfx_window);
+            self.need_font_recalc = false;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn window_resized(&mut self, gfx_window: &mut GfxWindow) {
+        self.update_font_metrics(gfx_window);
+    }
+
+    fn render(&self, _dt: f32, gfx_window: &mut GfxWindow) -> Result<(), Box<dyn Error>> {
+        gfx_window
+            .encoder
+            .clear(&gfx_window.quad_bundle.data.out_color, bg_color());
+        gfx_window
+            .encoder
+            .clear_depth(&gfx_window.quad_bundle.data.out_depth, 1.0);
+
+        let selected_tab = if self.showing_latency {
+            &self.latency_tab
+        } else {
+            &self.errors_tab
+        };
+        gfx_window.draw_outline(INPUT_OUTLINE_COLOR, &selected_tab.rect, 0.8, 2.0);
+        for key in &self.keys {
+            gfx_window.draw_quad(self.key_color(key), &key.rect, 1.0 - 0.1);
+            gfx_window.queue_label(&key.label);
+        }
+
+        gfx_window.queue_label(&self.back_label);
+        gfx_window.queue_label(&self.title);
+        gfx_window.queue_label(&self.errors_tab);
+        gfx_window.queue_label(&self.latency_tab);
+        gfx_window.queue_label(self.summary());
+
+        gfx_window
+            .glyph_brush
+            .use_queue()
+            .depth_target(&gfx_window.quad_bundle.data.out_depth)
+            .draw(
+                &mut gfx_window.encoder,
+                &gfx_window.quad_bundle.data.out_color,
+            )?;
+
+        Ok(())
+    }
+}
@@ -10,7 +10,10 @@ const MENU_FONT_SIZE: f32 = 48.0;
 pub struct Menu {
     need_font_recalc: bool,
     typing_test_label: Label,
+    practice_label: Label,
     results_list_label: Label,
+    stats_label: Label,
+    keyboard_label: Label,
 }
 
 impl Menu {
@@ -24,6 +27,13 @@ impl Menu {
                 String::from("Start typing test"),
                 gfx_window,
             ),
+            practice_label: Label::new(
+                MENU_FONT_SIZE,
+                gfx_window.fonts.roboto_font_id,
+                TEXT_COLOR,
+                String::from("Practice weak spots"),
+                gfx_window,
+            ),
             results_list_label: Label::new(
                 MENU_FONT_SIZE,
                 gfx_window.fonts.roboto_font_id,
@@ -31,6 +41,20 @@ impl Menu {
                 String::from("Results"),
                 gfx_window,
             ),
+            stats_label: Label::new(
+                MENU_FONT_SIZE,
+                gfx_window.fonts.roboto_font_id,
+                TEXT_COLOR,
+                String::from("Stats"),
+                gfx_window,
+            ),
+            keyboard_label: Label::new(
+                MENU_FONT_SIZE,
+                gfx_window.fonts.roboto_font_id,
+                TEXT_COLOR,
+                String::from("Weak keys"),
+                gfx_window,
+            ),
         }
     }
 
@@ -41,21 +65,45 @@ impl Menu {
             &mut self.typing_test_label.rect.position,
         );
 
+        ElementLayout::center_horizontally(
+            gfx_window.window_dim(),
+            self.practice_label.rect.bounds,
+            &mut self.practice_label.rect.position,
+        );
+
         ElementLayout::center_horizontally(
             gfx_window.window_dim(),
             self.results_list_label.rect.bounds,
             &mut self.results_list_label.rect.position,
         );
 
+        ElementLayout::center_horizontally(
+            gfx_window.window_dim(),
+            self.stats_label.rect.bounds,
+            &mut self.stats_label.rect.position,
+        );
+
+        ElementLayout::center_horizontally(
+            gfx_window.window_dim(),
+            self.keyboard_label.rect.bounds,
+            &mut self.keyboard_label.rect.position,
+        );
+
         {
             let mut v_centered = ElementLayout::vertical(gfx_window.window_dim());
             let typing_test_elem = v_centered.add_bounds(self.typing_test_label.rect.bounds);
+            let practice_elem = v_centered.add_bounds(self.practice_label.rect.bounds);
             let result_list_elem = v_centered.add_bounds(self.results_list_label.rect.bounds);
+            let stats_elem = v_centered.add_bounds(self.stats_label.rect.bounds);
+            let keyboard_elem = v_centered.add_bounds(self.keyboard_label.rect.bounds);
             v_centered.calc_positions();
             self.typing_test_label.rect.position.y =
                 v_centered.element_position(typing_test_elem).y;
+            self.practice_label.rect.position.y = v_centered.element_position(practice_elem).y;
             self.results_list_label.rect.position.y =
                 v_centered.element_position(result_list_elem).y;
+            self.stats_label.rect.position.y = v_centered.element_position(stats_elem).y;
+            self.keyboard_label.rect.position.y = v_centered.element_position(keyboard_elem).y;
         }
     }
 }
@@ -68,8 +116,16 @@ impl Screen for Menu {
     ) -> Option<Box<dyn Screen>> {
         if self.typing_test_label.ui_state.pressed {
             Some(Box::new(screens::TestScreen::new(gfx_window, config)))
+        } else if self.practice_label.ui_state.pressed {
+            Some(Box::new(screens::TestScreen::practice(gfx_window, config)))
         } else if self.results_list_label.ui_state.pressed {
-            Some(Box::new(screens::ResultsListScreen::new(gfx_window)))
+            Some(Box::new(screens::ResultsListScreen::new(
+                gfx_window, config,
+            )))
+        } else if self.stats_label.ui_state.pressed {
+            Some(Box::new(screens::StatsScreen::new(gfx_window, config)))
+        } else if self.keyboard_label.ui_state.pressed {
+            Some(Box::new(screens::KeyboardScreen::new(gfx_window, config)))
         } else {
             None
         }
@@ -86,8 +142,14 @@ impl Screen for Menu {
     fn mouse_click(&mut self, position: Vector2<f32>) {
         if self.typing_test_label.rect.contains_point(position) {
             self.typing_test_label.ui_state.pressed = true;
+        } else if self.practice_label.rect.contains_point(position) {
+            self.practice_label.ui_state.pressed = true;
         } else if self.results_list_label.rect.contains_point(position) {
             self.results_list_label.ui_state.pressed = true;
+        } else if self.stats_label.rect.contains_point(position) {
+            self.stats_label.ui_state.pressed = true;
+        } else if self.keyboard_label.rect.contains_point(position) {
+            self.keyboard_label.ui_state.pressed = true;
         }
     }
 
@@ -120,7 +182,10 @@ impl Screen for Menu {
             .clear_depth(&gfx_window.quad_bundle.data.out_depth, 1.0);
 
         gfx_window.queue_label(&self.typing_test_label);
+        gfx_window.queue_label(&self.practice_label);
         gfx_window.queue_label(&self.results_list_label);
+        gfx_window.queue_label(&self.stats_label);
+        gfx_window.queue_label(&self.keyboard_label);
 
         gfx_window
             .glyph_brush
@@ -9,6 +9,7 @@ const TITLE_FONT_SIZE: f32 = 48.0;
 const HEADER_FONT_SIZE: f32 = 32.0;
 const ROW_FONT_SIZE: f32 = 32.0;
 const TABLE_OUTLINE_WIDTH: f32 = 3.0;
+const RESULTS_PER_PAGE: usize = 15;
 
 const TABLE_OUTLINE_COLOR: ColorArray = MAGENTA;
 const TABLE_HEADER_UNDERLINE: ColorArray = MAGENTA;
@@ -34,7 +35,7 @@ fn table_cell_label(text: String, gfx_window: &mut GfxWindow) -> Label {
 }
 
 struct TableRow {
-    cells: [Label; 3],
+    cells: [Label; 4],
     row_rect: Rect,
     typing_result: TypingResult,
     ui_state: UIState,
@@ -49,11 +50,13 @@ impl TableRow {
         };
         let wpm = typing_result.wpm;
         let notes = typing_result.notes.clone();
+        let tags = typing_result.tags_text();
         Self {
             typing_result,
             cells: [
                 table_cell_label(datetime, gfx_window),
                 table_cell_label(format!("{}", wpm), gfx_window),
+                table_cell_label(tags, gfx_window),
                 table_cell_label(notes, gfx_window),
             ],
             row_rect: Rect::default(),
@@ -66,34 +69,28 @@ pub struct ResultsListScreen {
     need_font_recalc: bool,
     back_label: Label,
     list_title: Label,
-    table_headers: [Label; 3],
+    tag_filter: String, // typed on this screen, only results with the tag are listed
+    tag_filter_label: Label,
+    table_headers: [Label; 4],
     table_rows: Vec<TableRow>,
     table_rect: Rect,
     table_header_rect: Rect,
     table_rows_rect: Rect,
+    backend: Option<Box<dyn storage::ResultsBackend>>,
+    page: usize,
+    has_next_page: bool,
 }
 
 impl ResultsListScreen {
-    pub fn new(gfx_window: &mut GfxWindow) -> Self {
-        let mut read_typing_results = match storage::read_results_from_file() {
-            Ok(results) => results,
+    pub fn new(gfx_window: &mut GfxWindow, config: &Config) -> Self {
+        let backend = match storage::open_backend(config) {
+            Ok(backend) => Some(backend),
             Err(err) => {
-                println!("Error reading results from file: {:?}", err);
-                storage::ReadTypingResults::default()
+                println!("Error opening results: {:?}", err);
+                None
             }
         };
-        if read_typing_results.records_need_upgrading {
-            // TODO: do the record upgrade
-        }
-        let mut table_rows = Vec::new();
-        // TODO: Click on column to sort by that column
-        read_typing_results
-            .results
-            .sort_unstable_by_key(|result| -(result.time as i64));
-        for typing_result in read_typing_results.results {
-            table_rows.push(TableRow::new(gfx_window, typing_result));
-        }
-        Self {
+        let mut screen = Self {
             need_font_recalc: true,
             back_label: gfx_window.back_label(),
             list_title: Label::new(
@@ -103,16 +100,92 @@ impl ResultsListScreen {
                 String::from("Typing speed results:"),
                 gfx_window,
             ),
+            tag_filter: String::new(),
+            tag_filter_label: table_header_label(String::new(), gfx_window),
             table_headers: [
                 table_header_label(String::from("Date"), gfx_window),
                 table_header_label(String::from("WPM"), gfx_window),
+                table_header_label(String::from("Tags"), gfx_window),
                 table_header_label(String::from("Notes"), gfx_window),
             ],
-            table_rows,
+            table_rows: Vec::new(),
             table_rect: Rect::default(),
             table_header_rect: Rect::default(),
             table_rows_rect: Rect::default(),
+            backend,
+            page: 0,
+            has_next_page: false,
+        };
+        screen.load_page(gfx_window);
+        screen
+    }
+
+    // Only the results on the page are read, newest first
+    fn load_page(&mut self, gfx_window: &mut GfxWindow) {
+        // TODO: Click on column to sort by that column
+        let query = storage::ResultsQuery {
+            skip: self.page * RESULTS_PER_PAGE,
+            limit: Some(RESULTS_PER_PAGE + 1),
+            tag: Some(self.tag_filter.clone()).filter(|tag| !tag.trim().is_empty()),
+            ..storage::ResultsQuery::default()
+        };
+        let mut typing_results = match self.backend.as_mut().map(|backend| backend.query(&query)) {
+            Some(Ok(results)) => results,
+            Some(Err(err)) => {
+                println!("Error reading results from file: {:?}", err);
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        self.has_next_page = typing_results.len() > RESULTS_PER_PAGE;
+        typing_results.truncate(RESULTS_PER_PAGE);
+        self.table_rows = typing_results
+            .into_iter()
+            .map(|typing_result| TableRow::new(gfx_window, typing_result))
+            .collect();
+
+        let title = if self.page == 0 {
+            String::from("Typing speed results:")
+        } else {
+            format!("Typing speed results (page {}):", self.page + 1)
+        };
+        self.list_title.set_text(title, gfx_window);
+        self.list_title.recalc(gfx_window);
+        let tag_filter = if self.tag_filter.is_empty() {
+            String::from("Type to filter by tag")
+        } else {
+            format!("Tag: {}", self.tag_filter)
+        };
+        self.tag_filter_label.set_text(tag_filter, gfx_window);
+        self.tag_filter_label.recalc(gfx_window);
+        self.table_rect = Rect::default();
+        self.table_header_rect = Rect::default();
+        self.table_rows_rect = Rect::default();
+        self.need_font_recalc = true;
+    }
+
+    fn change_page(&mut self, forwards: bool, gfx_window: &mut GfxWindow) {
+        if forwards && self.has_next_page {
+            self.page += 1;
+        } else if !forwards && self.page > 0 {
+            self.page -= 1;
+        } else {
+            return;
         }
+        self.load_page(gfx_window);
+    }
+
+    fn change_tag_filter(&mut self, typed_char: Option<char>, gfx_window: &mut GfxWindow) {
+        match typed_char {
+            Some(typed_char) => self.tag_filter.push(typed_char),
+            None => {
+                if self.tag_filter.pop().is_none() {
+                    return;
+                }
+            }
+        }
+        self.page = 0;
+        self.load_page(gfx_window);
     }
 
     fn set_highlight_row(&mut self, hl_idx: Option<usize>) {
@@ -250,6 +323,10 @@ impl ResultsListScreen {
         horizontal_layout.calc_positions();
         self.list_title.rect.position = horizontal_layout.element_position(list_title_elem);
         self.list_title.rect.position.y = top_padding;
+        self.tag_filter_label.rect.position = vec2(
+            gfx_window.window_dim().x - self.tag_filter_label.rect.bounds.x - left_padding,
+            top_padding + title_height + 10.0,
+        );
 
         let top_of_table = top_padding + title_height + padding_between_heading_and_table;
         self.table_rect.position.y = top_of_table;
@@ -332,13 +409,17 @@ impl Screen for ResultsListScreen {
         }
     }
 
-    fn process_event(&mut self, event: &Event, _gfx_window: &mut GfxWindow) -> bool {
+    fn process_event(&mut self, event: &Event, gfx_window: &mut GfxWindow) -> bool {
         let mut update_and_render = false;
         if let Event::WindowEvent {
             event: win_event, ..
         } = event
         {
             match win_event {
+                WindowEvent::ReceivedCharacter(typed_char) if !typed_char.is_control() => {
+                    self.change_tag_filter(Some(*typed_char), gfx_window);
+                    update_and_render = true;
+                }
                 WindowEvent::KeyboardInput {
                     input: keyboard_input,
                     ..
@@ -355,6 +436,11 @@ impl Screen for ResultsListScreen {
                                     VirtualKeyCode::Down => self.move_highlight(1),
                                     VirtualKeyCode::Up => self.move_highlight(-1),
                                     VirtualKeyCode::Return => self.select_highlighted_row(),
+                                    VirtualKeyCode::PageDown => self.change_page(true, gfx_window),
+                                    VirtualKeyCode::PageUp => self.change_page(false, gfx_window),
+                                    VirtualKeyCode::Back => {
+                                        self.change_tag_filter(None, gfx_window)
+                                    }
                                     _ => {}
                                 }
                                 update_and_render = true;
@@ -440,6 +526,7 @@ impl Screen for ResultsListScreen {
 
         gfx_window.queue_label(&self.back_label);
         gfx_window.queue_label(&self.list_title);
+        gfx_window.queue_label(&self.tag_filter_label);
 
         for header_label in &self.table_headers {
             gfx_window.queue_label(header_label);
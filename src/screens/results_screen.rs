@@ -8,11 +8,22 @@ use std::error::Error;
 const NORMAL_LABEL_FONT_SIZE: f32 = 32.0;
 const HEADLINE_LABEL_FONT_SIZE: f32 = 48.0;
 const HEADLINE_VALUE_FONT_SIZE: f32 = 48.0;
+const LEGEND_FONT_SIZE: f32 = 24.0;
+
+const TIMELINE_HEIGHT: f32 = 120.0;
+const AXIS_WIDTH: f32 = 2.0;
+const TIMELINE_WIDTH: f32 = 2.0;
+const AXIS_COLOR: ColorArray = BASE1;
+const RAW_WPM_COLOR: ColorArray = BLUE;
+const NET_WPM_COLOR: ColorArray = ORANGE;
 
 #[derive(Default)]
 pub struct ResultsScreen {
     typing_result: TypingResult,
     unsaved_result: bool,
+    stored_result: bool,
+    confirming_delete: bool,
+    confirming_leave: bool,
     need_font_recalc: bool,
     wpm_label: Label,
     wpm_value: Label,
@@ -22,10 +33,27 @@ pub struct ResultsScreen {
     incorrect_value: Label,
     backspaces_label: Label,
     backspaces_value: Label,
+    consistency_label: Label,
+    consistency_value: Label,
+    tags_label: Label,
+    tags_value: Label,
+    tags_text: String,  // as typed, `typing_result.tags` is parsed from it
+    editing_tags: bool, // typing goes to the notes otherwise
     notes_label: Label,
     notes_value: Label,
     back_label: Label,
     save_label: Label,
+    delete_label: Label,
+    confirm_delete_label: Label,
+    confirm_leave_label: Label,
+    recovered_label: Option<Label>,
+    // each second of the test as (seconds in, WPM)
+    timeline_chart: Chart,
+    timeline_mesh: Mesh, // rebuilt when the chart is laid out
+    raw_speeds: Vec<(f64, f64)>,
+    net_speeds: Vec<(f64, f64)>,
+    raw_legend: Label,
+    net_legend: Label,
 }
 
 impl ResultsScreen {
@@ -34,9 +62,37 @@ impl ResultsScreen {
         unsaved_result: bool,
         gfx_window: &mut GfxWindow,
     ) -> Self {
+        let speeds = |wpm: fn(&WpmSample) -> f32| {
+            typing_result
+                .timeline
+                .iter()
+                .enumerate()
+                .map(|(second, sample)| ((second + 1) as f64, f64::from(wpm(sample))))
+                .collect::<Vec<_>>()
+        };
+        let raw_speeds = speeds(|sample| sample.raw_wpm);
+        let net_speeds = speeds(|sample| sample.net_wpm);
+        // round up to tens so the lines don't touch the top
+        let top = raw_speeds
+            .iter()
+            .chain(&net_speeds)
+            .map(|&(_, wpm)| wpm)
+            .fold(0.0, f64::max);
+        let timeline_chart = Chart::new(
+            Rect::default(),
+            (1.0, raw_speeds.len() as f64),
+            (0.0, ((top / 10.0).floor() + 1.0) * 10.0),
+        );
+        let consistency = typing_result
+            .consistency()
+            .map_or_else(|| String::from("-"), |score| format!("{:.0}%", score));
+
         Self {
             typing_result: typing_result.clone(),
             unsaved_result,
+            stored_result: !unsaved_result,
+            confirming_delete: false,
+            confirming_leave: false,
             need_font_recalc: true,
             wpm_label: Label::new(
                 HEADLINE_LABEL_FONT_SIZE,
@@ -94,6 +150,36 @@ impl ResultsScreen {
                 format!("{}", typing_result.backspaces),
                 gfx_window,
             ),
+            consistency_label: Label::new(
+                NORMAL_LABEL_FONT_SIZE,
+                gfx_window.fonts.roboto_font_id,
+                TEXT_COLOR,
+                String::from("Consistency"),
+                gfx_window,
+            ),
+            consistency_value: Label::new(
+                NORMAL_LABEL_FONT_SIZE,
+                gfx_window.fonts.iosevka_font_id,
+                TEXT_COLOR,
+                consistency,
+                gfx_window,
+            ),
+            tags_label: Label::new(
+                NORMAL_LABEL_FONT_SIZE,
+                gfx_window.fonts.roboto_font_id,
+                TEXT_COLOR,
+                String::from("Tags"),
+                gfx_window,
+            ),
+            tags_value: Label::new(
+                NORMAL_LABEL_FONT_SIZE,
+                gfx_window.fonts.iosevka_font_id,
+                TEXT_COLOR,
+                typing_result.tags_text(),
+                gfx_window,
+            ),
+            tags_text: typing_result.tags_text(),
+            editing_tags: false,
             notes_label: Label::new(
                 NORMAL_LABEL_FONT_SIZE,
                 gfx_window.fonts.roboto_font_id,
@@ -116,10 +202,85 @@ impl ResultsScreen {
                 String::from("Save"),
                 gfx_window,
             ),
+            delete_label: Label::new(
+                NORMAL_LABEL_FONT_SIZE,
+                gfx_window.fonts.iosevka_font_id,
+                TEXT_COLOR,
+                String::from("Delete"),
+                gfx_window,
+            ),
+            confirm_delete_label: Label::new(
+                NORMAL_LABEL_FONT_SIZE,
+                gfx_window.fonts.iosevka_font_id,
+                INCORRECT_WORD_COLOR,
+                String::from("Really delete?"),
+                gfx_window,
+            ),
+            confirm_leave_label: Label::new(
+                NORMAL_LABEL_FONT_SIZE,
+                gfx_window.fonts.iosevka_font_id,
+                INCORRECT_WORD_COLOR,
+                String::from("Leave without saving?"),
+                gfx_window,
+            ),
+            recovered_label: None,
+            timeline_chart,
+            timeline_mesh: Mesh::default(),
+            raw_speeds,
+            net_speeds,
+            raw_legend: Label::new(
+                LEGEND_FONT_SIZE,
+                gfx_window.fonts.roboto_font_id,
+                RAW_WPM_COLOR,
+                String::from("Raw"),
+                gfx_window,
+            ),
+            net_legend: Label::new(
+                LEGEND_FONT_SIZE,
+                gfx_window.fonts.roboto_font_id,
+                NET_WPM_COLOR,
+                String::from("Net"),
+                gfx_window,
+            ),
+        }
+    }
+
+    // Offers back a result from the journal, left by a crash or quitting
+    pub fn recovered(entry: storage::JournalEntry, gfx_window: &mut GfxWindow) -> Self {
+        let notice = match entry {
+            storage::JournalEntry::InProgress(_) => "Recovered an unfinished test, save it?",
+            storage::JournalEntry::Finished(_) => "Recovered an unsaved result, save it?",
+        };
+        let recovered_label = Label::new(
+            NORMAL_LABEL_FONT_SIZE,
+            gfx_window.fonts.roboto_font_id,
+            TEXT_COLOR,
+            String::from(notice),
+            gfx_window,
+        );
+        Self {
+            recovered_label: Some(recovered_label),
+            ..Self::new(entry.typing_result().clone(), true, gfx_window)
+        }
+    }
+
+    fn clear_journal(&self, config: &Config) {
+        if self.stored_result {
+            return;
+        }
+        if let Err(error) =
+            storage::results_path(config).and_then(|path| storage::clear_journal(&path))
+        {
+            println!("Error clearing the journal: {:?}", error);
         }
     }
 
     fn type_char(&mut self, typed: char, gfx_window: &mut GfxWindow) {
+        if self.editing_tags {
+            self.tags_text.push(typed);
+            self.tags_changed(gfx_window);
+            return;
+        }
         self.typing_result.notes.push(typed);
         self.notes_value
             .set_text(self.typing_result.notes.clone(), gfx_window);
@@ -128,6 +289,11 @@ impl ResultsScreen {
     }
 
     fn type_backspace(&mut self, gfx_window: &mut GfxWindow) {
+        if self.editing_tags {
+            let _ = self.tags_text.pop();
+            self.tags_changed(gfx_window);
+            return;
+        }
         let _ = self.typing_result.notes.pop();
         self.notes_value
             .set_text(self.typing_result.notes.clone(), gfx_window);
@@ -135,12 +301,42 @@ impl ResultsScreen {
         self.unsaved_result = true;
     }
 
+    fn tags_changed(&mut self, gfx_window: &mut GfxWindow) {
+        self.typing_result.tags = parse_tags(&self.tags_text);
+        self.tags_value.set_text(self.tags_text.clone(), gfx_window);
+        self.tags_value.recalc(gfx_window);
+        self.unsaved_result = true;
+    }
+
+    fn build_timeline_mesh(&mut self) {
+        let mesh = &mut self.timeline_mesh;
+        mesh.clear();
+        self.timeline_chart
+            .add_axes(mesh, AXIS_COLOR, 1.0 - 0.1, AXIS_WIDTH);
+        self.timeline_chart.add_line(
+            mesh,
+            &self.raw_speeds,
+            RAW_WPM_COLOR,
+            1.0 - 0.2,
+            TIMELINE_WIDTH,
+        );
+        self.timeline_chart.add_line(
+            mesh,
+            &self.net_speeds,
+            NET_WPM_COLOR,
+            1.0 - 0.3,
+            TIMELINE_WIDTH,
+        );
+    }
+
     fn update_font_metrics(&mut self, gfx_window: &mut GfxWindow) {
         let longest_width_of_labels = [
             &self.wpm_label,
             &self.correct_label,
             &self.incorrect_label,
             &self.backspaces_label,
+            &self.consistency_label,
+            &self.tags_label,
             &self.notes_label,
         ]
         .iter()
@@ -157,6 +353,8 @@ impl ResultsScreen {
             &self.correct_value,
             &self.incorrect_value,
             &self.backspaces_value,
+            &self.consistency_value,
+            &self.tags_value,
             &self.notes_value,
         ]
         .iter()
@@ -195,6 +393,17 @@ impl ResultsScreen {
         );
         backspaces_rect.bounds.x = line_width;
 
+        let mut consistency_rect = Rect::default();
+        consistency_rect.bounds.y = f32::max(
+            self.consistency_label.rect.bounds.y,
+            self.consistency_value.rect.bounds.y,
+        );
+        consistency_rect.bounds.x = line_width;
+
+        let mut tags_rect = Rect::default();
+        tags_rect.bounds.y = f32::max(self.tags_label.rect.bounds.y, self.tags_value.rect.bounds.y);
+        tags_rect.bounds.x = line_width;
+
         let mut notes_rect = Rect::default();
         notes_rect.bounds.y = f32::max(
             self.notes_label.rect.bounds.y,
@@ -213,8 +422,19 @@ impl ResultsScreen {
         let _ = vertical_layout.add_bounds(padding_rect);
         let backspaces_rect_elem = vertical_layout.add_bounds(backspaces_rect.bounds);
         let _ = vertical_layout.add_bounds(padding_rect);
+        let consistency_rect_elem = vertical_layout.add_bounds(consistency_rect.bounds);
+        let _ = vertical_layout.add_bounds(padding_rect);
+        let tags_rect_elem = vertical_layout.add_bounds(tags_rect.bounds);
+        let _ = vertical_layout.add_bounds(padding_rect);
         let notes_rect_elem = vertical_layout.add_bounds(notes_rect.bounds);
         let _ = vertical_layout.add_bounds(padding_rect);
+        let timeline_bounds = if self.raw_speeds.len() > 1 {
+            vec2(line_width, TIMELINE_HEIGHT)
+        } else {
+            vec2(line_width, 0.0)
+        };
+        let timeline_rect_elem = vertical_layout.add_bounds(timeline_bounds);
+        let _ = vertical_layout.add_bounds(padding_rect);
         let save_rect_elem = vertical_layout.add_bounds(self.save_label.rect.bounds);
         vertical_layout.calc_positions();
         self.wpm_label.rect.position = vertical_layout.element_position(result_rect_elem);
@@ -222,12 +442,17 @@ impl ResultsScreen {
         self.incorrect_label.rect.position = vertical_layout.element_position(incorrect_rect_elem);
         self.backspaces_label.rect.position =
             vertical_layout.element_position(backspaces_rect_elem);
+        self.consistency_label.rect.position =
+            vertical_layout.element_position(consistency_rect_elem);
+        self.tags_label.rect.position = vertical_layout.element_position(tags_rect_elem);
         self.notes_label.rect.position = vertical_layout.element_position(notes_rect_elem);
         self.save_label.rect.position = vertical_layout.element_position(save_rect_elem);
         self.wpm_value.rect.position.y = self.wpm_label.rect.position.y;
         self.correct_value.rect.position.y = self.correct_label.rect.position.y;
         self.incorrect_value.rect.position.y = self.incorrect_label.rect.position.y;
         self.backspaces_value.rect.position.y = self.backspaces_label.rect.position.y;
+        self.consistency_value.rect.position.y = self.consistency_label.rect.position.y;
+        self.tags_value.rect.position.y = self.tags_label.rect.position.y;
         self.notes_value.rect.position.y = self.notes_label.rect.position.y;
 
         let mut horizontal_layout = ElementLayout::horizontal(gfx_window.window_dim());
@@ -239,6 +464,8 @@ impl ResultsScreen {
         self.correct_label.rect.position.x = left_margin;
         self.incorrect_label.rect.position.x = left_margin;
         self.backspaces_label.rect.position.x = left_margin;
+        self.consistency_label.rect.position.x = left_margin;
+        self.tags_label.rect.position.x = left_margin;
         self.notes_label.rect.position.x = left_margin;
         self.save_label.rect.position.x = left_margin;
 
@@ -251,9 +478,44 @@ impl ResultsScreen {
             left_margin + vertical_padding + longest_width_of_labels;
         self.backspaces_value.rect.position.x =
             left_margin + vertical_padding + longest_width_of_labels;
+        self.consistency_value.rect.position.x =
+            left_margin + vertical_padding + longest_width_of_labels;
+        self.tags_value.rect.position.x = left_margin + vertical_padding + longest_width_of_labels;
         self.notes_value.rect.position.x = left_margin + vertical_padding + longest_width_of_labels;
 
+        self.timeline_chart.rect = Rect::new(
+            vec2(
+                left_margin,
+                vertical_layout.element_position(timeline_rect_elem).y,
+            ),
+            timeline_bounds,
+        );
+        self.build_timeline_mesh();
+        // the key sits in the top right corner of the chart
+        let chart_right = self.timeline_chart.rect.right_edge();
+        let chart_top = self.timeline_chart.rect.position.y;
+        self.net_legend.rect.position =
+            vec2(chart_right - self.net_legend.rect.bounds.x, chart_top);
+        self.raw_legend.rect.position = vec2(
+            self.net_legend.rect.position.x - self.raw_legend.rect.bounds.x - vertical_padding,
+            chart_top,
+        );
+
         self.back_label.rect.position = vec2(20.0, 20.0);
+        self.confirm_leave_label.rect.position =
+            vec2(self.back_label.rect.right_edge() + 20.0, 20.0);
+
+        let window_width = gfx_window.window_dim().x;
+        self.delete_label.rect.position =
+            vec2(window_width - self.delete_label.rect.bounds.x - 20.0, 20.0);
+        self.confirm_delete_label.rect.position = vec2(
+            window_width - self.confirm_delete_label.rect.bounds.x - 20.0,
+            20.0,
+        );
+        if let Some(recovered_label) = &mut self.recovered_label {
+            recovered_label.rect.position =
+                vec2((window_width - recovered_label.rect.bounds.x) / 2.0, 20.0);
+        }
     }
 }
 
@@ -264,29 +526,77 @@ impl Screen for ResultsScreen {
         config: &Config,
     ) -> Option<Box<dyn Screen>> {
         if self.back_label.ui_state.pressed {
+            // going back without saving throws the result away
+            self.clear_journal(config);
             let screen = screens::TestScreen::new(gfx_window, config);
             Some(Box::new(screen))
         } else if self.save_label.ui_state.pressed {
-            match storage::save_result_to_file(&self.typing_result) {
+            let saved = storage::open_backend(config).and_then(|mut backend| {
+                if self.stored_result {
+                    backend.update(&self.typing_result)
+                } else {
+                    backend.append(&self.typing_result)
+                }
+            });
+            match saved {
                 Err(error) => {
                     println!("Error saving results to file: {:?}", error);
                 }
-                _ => {}
+                _ => self.clear_journal(config),
             };
-            Some(Box::new(screens::ResultsListScreen::new(gfx_window)))
+            Some(Box::new(screens::ResultsListScreen::new(
+                gfx_window, config,
+            )))
+        } else if self.confirm_delete_label.ui_state.pressed {
+            let deleted = storage::open_backend(config)
+                .and_then(|mut backend| backend.delete(self.typing_result.id));
+            if let Err(error) = deleted {
+                println!("Error deleting result from file: {:?}", error);
+            }
+            Some(Box::new(screens::ResultsListScreen::new(
+                gfx_window, config,
+            )))
         } else {
             None
         }
     }
 
     fn mouse_click(&mut self, position: Vector2<f32>) {
-        if self.back_label.rect.contains_point(position) {
+        // clicking anywhere else means staying
+        let confirming_leave = std::mem::replace(&mut self.confirming_leave, false);
+        if confirming_leave && self.confirm_leave_label.rect.contains_point(position) {
             self.back_label.ui_state.pressed = true;
+        } else if self.back_label.rect.contains_point(position) {
+            if self.unsaved_result {
+                self.confirming_leave = true;
+            } else {
+                self.back_label.ui_state.pressed = true;
+            }
+        } else if self.tags_label.rect.contains_point(position)
+            || self.tags_value.rect.contains_point(position)
+        {
+            self.editing_tags = true;
+        } else if self.notes_label.rect.contains_point(position)
+            || self.notes_value.rect.contains_point(position)
+        {
+            self.editing_tags = false;
         } else if self.unsaved_result && self.save_label.rect.contains_point(position) {
             self.save_label.ui_state.pressed = true;
+        } else if self.stored_result && self.confirming_delete {
+            if self.confirm_delete_label.rect.contains_point(position) {
+                self.confirm_delete_label.ui_state.pressed = true;
+            } else {
+                self.confirming_delete = false;
+            }
+        } else if self.stored_result && self.delete_label.rect.contains_point(position) {
+            self.confirming_delete = true;
         }
     }
 
+    fn has_unsaved_work(&self) -> bool {
+        self.unsaved_result
+    }
+
     fn process_event(&mut self, event: &Event, gfx_window: &mut GfxWindow) -> bool {
         let mut update_and_render = false;
         if let Event::WindowEvent {
@@ -350,6 +660,21 @@ impl Screen for ResultsScreen {
             .encoder
             .clear_depth(&gfx_window.quad_bundle.data.out_depth, 1.0);
 
+        // around the field typing goes to
+        let editing_label = if self.editing_tags {
+            &self.tags_label
+        } else {
+            &self.notes_label
+        };
+        gfx_window.draw_outline(INPUT_OUTLINE_COLOR, &editing_label.rect, 0.8, 2.0);
+
+        // tests from before the timeline was kept have no graph
+        if self.raw_speeds.len() > 1 {
+            gfx_window.draw_mesh(&self.timeline_mesh);
+            gfx_window.queue_label(&self.raw_legend);
+            gfx_window.queue_label(&self.net_legend);
+        }
+
         let labels = [
             &self.back_label, // FIXME: Move to app-level navigation
             &self.wpm_label,
@@ -360,6 +685,10 @@ impl Screen for ResultsScreen {
             &self.incorrect_value,
             &self.backspaces_label,
             &self.backspaces_value,
+            &self.consistency_label,
+            &self.consistency_value,
+            &self.tags_label,
+            &self.tags_value,
             &self.notes_label,
             &self.notes_value,
         ];
@@ -371,6 +700,18 @@ impl Screen for ResultsScreen {
             gfx_window.queue_label(&self.save_label);
         }
 
+        if self.confirming_leave {
+            gfx_window.queue_label(&self.confirm_leave_label);
+        } else if let Some(recovered_label) = &self.recovered_label {
+            gfx_window.queue_label(recovered_label);
+        }
+
+        if self.confirming_delete {
+            gfx_window.queue_label(&self.confirm_delete_label);
+        } else if self.stored_result {
+            gfx_window.queue_label(&self.delete_label);
+        }
+
         gfx_window
             .glyph_brush
             .use_queue()
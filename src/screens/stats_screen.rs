@@ -0,0 +1,351 @@
+use crate::layout::ElementLayout;
+use crate::screens;
+use crate::*;
+use cgmath::*;
+use std::error::Error;
+
+const TITLE_FONT_SIZE: f32 = 48.0;
+const SUMMARY_FONT_SIZE: f32 = 32.0;
+const AXIS_FONT_SIZE: f32 = 24.0;
+const MOVING_AVERAGE_TESTS: usize = 10;
+const NGRAMS_SHOWN: usize = 3;
+
+const AXIS_WIDTH: f32 = 3.0;
+const AVERAGE_WIDTH: f32 = 3.0;
+const POINT_SIZE: f32 = 8.0;
+const PERSONAL_BEST_SIZE: f32 = 16.0;
+
+const AXIS_COLOR: ColorArray = BASE1;
+const POINT_COLOR: ColorArray = BLUE;
+const PERSONAL_BEST_COLOR: ColorArray = ORANGE;
+const AVERAGE_COLOR: ColorArray = MAGENTA;
+
+fn axis_label(text: String, gfx_window: &mut GfxWindow) -> Label {
+    Label::new(
+        AXIS_FONT_SIZE,
+        gfx_window.fonts.roboto_font_id,
+        TEXT_COLOR,
+        text,
+        gfx_window,
+    )
+}
+
+// The weakest pairs and triples of letters, e.g. "Slowest: th 212 ms, ..."
+fn weakest_ngrams_text(
+    heading: &str,
+    results: &[TypingResult],
+    weakest: fn(&[stats::NgramStats], usize) -> Vec<&stats::NgramStats>,
+    describe: fn(&stats::NgramStats) -> String,
+) -> String {
+    let groups = [2, 3]
+        .iter()
+        .map(|&n| {
+            let ngrams = stats::ngram_stats(results, n);
+            weakest(&ngrams, NGRAMS_SHOWN)
+                .into_iter()
+                .map(|ngram| format!("{} {}", ngram.ngram, describe(ngram)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .filter(|group| !group.is_empty())
+        .collect::<Vec<_>>();
+    if groups.is_empty() {
+        String::new()
+    } else {
+        format!("{}: {}", heading, groups.join("; "))
+    }
+}
+
+// WPM over time, every result oldest first, with summary numbers above
+pub struct StatsScreen {
+    need_font_recalc: bool,
+    back_label: Label,
+    title: Label,
+    summary_label: Label,
+    slowest_ngrams_label: Label,
+    error_prone_ngrams_label: Label,
+    max_wpm_label: Label,
+    min_wpm_label: Label,
+    first_date_label: Label,
+    last_date_label: Label,
+    chart: Chart,
+    chart_mesh: Mesh, // rebuilt when the chart is laid out
+    // (time in milliseconds, WPM) for plotting
+    speeds: Vec<(f64, f64)>,
+    moving_average: Vec<(f64, f64)>,
+    personal_bests: Vec<(f64, f64)>, // when each was set
+}
+
+impl StatsScreen {
+    pub fn new(gfx_window: &mut GfxWindow, config: &Config) -> Self {
+        let mut results = match storage::open_backend(config).and_then(|mut backend| backend.list())
+        {
+            Ok(results) => results,
+            Err(err) => {
+                println!("Error reading results: {:?}", err);
+                Vec::new()
+            }
+        };
+        results.sort_by_key(|typing_result| typing_result.timestamp_ms());
+
+        let summary = match stats::Summary::of(&results) {
+            Some(summary) => format!(
+                "{} tests, mean {:.1}, median {:.1}, best {} wpm, {:.1}% accuracy",
+                summary.count,
+                summary.mean_wpm,
+                summary.median_wpm,
+                summary.best_wpm,
+                summary.accuracy * 100.0
+            ),
+            None => String::from("No results yet"),
+        };
+        // round out to tens so the axis labels are tidy
+        let min_wpm = results.iter().map(|typing_result| typing_result.wpm).min();
+        let max_wpm = results.iter().map(|typing_result| typing_result.wpm).max();
+        let bottom = min_wpm.map_or(0, |wpm| (wpm.max(0) / 10) * 10);
+        let top = max_wpm.map_or(100, |wpm| (wpm / 10 + 1) * 10);
+        let date = |typing_result: Option<&TypingResult>| {
+            typing_result
+                .and_then(|typing_result| typing_result.datetime())
+                .map(|datetime| datetime.format("%Y-%m-%d").to_string())
+                .unwrap_or_default()
+        };
+
+        let times = results
+            .iter()
+            .map(|typing_result| typing_result.timestamp_ms() as f64)
+            .collect::<Vec<_>>();
+        let speeds = times
+            .iter()
+            .zip(&results)
+            .map(|(&time, typing_result)| (time, f64::from(typing_result.wpm)))
+            .collect::<Vec<_>>();
+        let moving_average = times
+            .iter()
+            .copied()
+            .zip(stats::moving_average(&results, MOVING_AVERAGE_TESTS))
+            .collect();
+        let personal_bests = speeds
+            .iter()
+            .zip(stats::set_personal_best(&results))
+            .filter(|(_, is_best)| *is_best)
+            .map(|(&speed, _)| speed)
+            .collect();
+        let slowest_ngrams =
+            weakest_ngrams_text("Slowest", &results, stats::slowest_ngrams, |ngram| {
+                format!("{:.0} ms", ngram.mean_latency_ms.unwrap_or_default())
+            });
+        let error_prone_ngrams = weakest_ngrams_text(
+            "Most errors",
+            &results,
+            stats::most_error_prone_ngrams,
+            |ngram| format!("{:.0}%", ngram.error_rate() * 100.0),
+        );
+        let time_range = (
+            times.first().copied().unwrap_or_default(),
+            times.last().copied().unwrap_or_default(),
+        );
+
+        Self {
+            need_font_recalc: true,
+            back_label: gfx_window.back_label(),
+            title: Label::new(
+                TITLE_FONT_SIZE,
+                gfx_window.fonts.roboto_font_id,
+                TEXT_COLOR,
+                String::from("Typing speed over time"),
+                gfx_window,
+            ),
+            summary_label: Label::new(
+                SUMMARY_FONT_SIZE,
+                gfx_window.fonts.roboto_font_id,
+                TEXT_COLOR,
+                summary,
+                gfx_window,
+            ),
+            slowest_ngrams_label: axis_label(slowest_ngrams, gfx_window),
+            error_prone_ngrams_label: axis_label(error_prone_ngrams, gfx_window),
+            max_wpm_label: axis_label(format!("{}", top), gfx_window),
+            min_wpm_label: axis_label(format!("{}", bottom), gfx_window),
+            first_date_label: axis_label(date(results.first()), gfx_window),
+            last_date_label: axis_label(date(results.last()), gfx_window),
+            chart: Chart::new(
+                Rect::default(),
+                time_range,
+                (f64::from(bottom), f64::from(top)),
+            ),
+            chart_mesh: Mesh::default(),
+            speeds,
+            moving_average,
+            personal_bests,
+        }
+    }
+
+    fn update_font_metrics(&mut self, gfx_window: &mut GfxWindow) {
+        let top_padding = 30.0;
+        let left_padding = 15.0;
+        let right_padding = 40.0;
+        let label_gap = 10.0;
+
+        self.back_label.rect.position = vec2(left_padding, top_padding);
+        ElementLayout::center_horizontally(
+            gfx_window.window_dim(),
+            self.title.rect.bounds,
+            &mut self.title.rect.position,
+        );
+        self.title.rect.position.y = top_padding;
+        ElementLayout::center_horizontally(
+            gfx_window.window_dim(),
+            self.summary_label.rect.bounds,
+            &mut self.summary_label.rect.position,
+        );
+        self.summary_label.rect.position.y =
+            top_padding + self.title.rect.bounds.y + label_gap * 2.0;
+        let mut ngrams_top =
+            self.summary_label.rect.position.y + self.summary_label.rect.bounds.y + label_gap;
+        for label in [
+            &mut self.slowest_ngrams_label,
+            &mut self.error_prone_ngrams_label,
+        ]
+        .iter_mut()
+        {
+            ElementLayout::center_horizontally(
+                gfx_window.window_dim(),
+                label.rect.bounds,
+                &mut label.rect.position,
+            );
+            label.rect.position.y = ngrams_top;
+            ngrams_top += label.rect.bounds.y;
+        }
+
+        let axis_label_width = f32::max(
+            self.max_wpm_label.rect.bounds.x,
+            self.min_wpm_label.rect.bounds.x,
+        );
+        let chart_top = ngrams_top + self.max_wpm_label.rect.bounds.y + label_gap * 2.0;
+        let chart_bottom = gfx_window.window_dim().y
+            - self.first_date_label.rect.bounds.y
+            - top_padding
+            - label_gap;
+        let chart_left = left_padding + axis_label_width + label_gap;
+        self.chart.rect = Rect::new(
+            vec2(chart_left, chart_top),
+            vec2(
+                gfx_window.window_dim().x - chart_left - right_padding,
+                (chart_bottom - chart_top).max(0.0),
+            ),
+        );
+
+        let chart = self.chart.rect;
+        self.max_wpm_label.rect.position = vec2(
+            chart.position.x - self.max_wpm_label.rect.bounds.x - label_gap,
+            chart.position.y - self.max_wpm_label.rect.bounds.y / 2.0,
+        );
+        self.min_wpm_label.rect.position = vec2(
+            chart.position.x - self.min_wpm_label.rect.bounds.x - label_gap,
+            chart.position.y + chart.bounds.y - self.min_wpm_label.rect.bounds.y / 2.0,
+        );
+        let below_chart = chart.position.y + chart.bounds.y + label_gap;
+        self.first_date_label.rect.position = vec2(chart.position.x, below_chart);
+        self.last_date_label.rect.position = vec2(
+            chart.right_edge() - self.last_date_label.rect.bounds.x,
+            below_chart,
+        );
+        self.build_chart_mesh();
+    }
+
+    // Drawn back to front, the points on top of the line
+    fn build_chart_mesh(&mut self) {
+        let mesh = &mut self.chart_mesh;
+        mesh.clear();
+        self.chart.add_axes(mesh, AXIS_COLOR, 1.0 - 0.1, AXIS_WIDTH);
+        self.chart.add_line(
+            mesh,
+            &self.moving_average,
+            AVERAGE_COLOR,
+            1.0 - 0.2,
+            AVERAGE_WIDTH,
+        );
+        self.chart.add_markers(
+            mesh,
+            &self.personal_bests,
+            PERSONAL_BEST_COLOR,
+            1.0 - 0.3,
+            PERSONAL_BEST_SIZE,
+        );
+        self.chart
+            .add_markers(mesh, &self.speeds, POINT_COLOR, 1.0 - 0.4, POINT_SIZE);
+    }
+}
+
+impl Screen for StatsScreen {
+    fn maybe_change_to_screen(
+        &self,
+        gfx_window: &mut GfxWindow,
+        _config: &Config,
+    ) -> Option<Box<dyn Screen>> {
+        if self.back_label.ui_state.pressed {
+            Some(Box::new(screens::Menu::new(gfx_window)))
+        } else {
+            None
+        }
+    }
+
+    fn mouse_click(&mut self, position: Vector2<f32>) {
+        if self.back_label.rect.contains_point(position) {
+            self.back_label.ui_state.pressed = true;
+        }
+    }
+
+    fn update(
+        &mut self,
+        _dt: f32,
+        _mouse_position: Vector2<f32>,
+        _config: &Config,
+        gfx_window: &mut GfxWindow,
+    ) -> bool {
+        if self.need_font_recalc {
+            self.update_font_metrics(gfx_window);
+            self.need_font_recalc = false;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn window_resized(&mut self, gfx_window: &mut GfxWindow) {
+        self.update_font_metrics(gfx_window);
+    }
+
+    fn render(&self, _dt: f32, gfx_window: &mut GfxWindow) -> Result<(), Box<dyn Error>> {
+        gfx_window
+            .encoder
+            .clear(&gfx_window.quad_bundle.data.out_color, bg_color());
+        gfx_window
+            .encoder
+            .clear_depth(&gfx_window.quad_bundle.data.out_depth, 1.0);
+
+        gfx_window.draw_mesh(&self.chart_mesh);
+
+        gfx_window.queue_label(&self.back_label);
+        gfx_window.queue_label(&self.title);
+        gfx_window.queue_label(&self.summary_label);
+        gfx_window.queue_label(&self.slowest_ngrams_label);
+        gfx_window.queue_label(&self.error_prone_ngrams_label);
+        gfx_window.queue_label(&self.max_wpm_label);
+        gfx_window.queue_label(&self.min_wpm_label);
+        gfx_window.queue_label(&self.first_date_label);
+        gfx_window.queue_label(&self.last_date_label);
+
+        gfx_window
+            .glyph_brush
+            .use_queue()
+            .depth_target(&gfx_window.quad_bundle.data.out_depth)
+            .draw(
+                &mut gfx_window.encoder,
+                &gfx_window.quad_bundle.data.out_color,
+            )?;
+
+        Ok(())
+    }
+}
@@ -7,6 +7,7 @@ use gfx_glyph::{
 };
 use glutin::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 use std::error::Error;
+use std::path::PathBuf;
 
 const INPUT_FONT_SIZE: f32 = 32.0;
 const REFERENCE_FONT_SIZE: f32 = 32.0;
@@ -29,10 +30,25 @@ pub struct TestScreen {
     reference_cursor: Rect,
     input_cursor_size: Label,
     reference_cursor_size: Label,
+    finished_result: Option<TypingResult>,
+    auto_saved: bool,
+    journal_path: Option<PathBuf>, // the results file the journal is kept next to
+    journal: Option<storage::JournalWriter>, // for the test in progress
+    practice: bool,
 }
 
 impl TestScreen {
     pub fn new(gfx_window: &mut GfxWindow, config: &Config) -> Self {
+        Self::with_mode(gfx_window, config, false)
+    }
+
+    // A test of the words with the weakest keys and bigrams, and the ones
+    // that were missed before
+    pub fn practice(gfx_window: &mut GfxWindow, config: &Config) -> Self {
+        Self::with_mode(gfx_window, config, true)
+    }
+
+    fn with_mode(gfx_window: &mut GfxWindow, config: &Config, practice: bool) -> Self {
         let input_label = Label::new(
             INPUT_FONT_SIZE,
             gfx_window.fonts.roboto_font_id,
@@ -85,6 +101,11 @@ impl TestScreen {
             ),
             timer_label,
             reference_text_label,
+            journal_path: storage::results_path(config).ok(),
+            journal: storage::results_path(config)
+                .ok()
+                .map(|path| storage::JournalWriter::new(&path)),
+            practice,
             ..TestScreen::default()
         };
         test_screen.start_test(config);
@@ -92,7 +113,17 @@ impl TestScreen {
     }
 
     fn start_test(&mut self, config: &Config) {
-        self.typing_test.top200();
+        if self.practice {
+            match storage::open_backend(config).and_then(|mut backend| backend.list()) {
+                Ok(results) => self.typing_test.practice(&results),
+                Err(err) => {
+                    println!("Error reading results: {:?}", err);
+                    self.typing_test.top200();
+                }
+            }
+        } else {
+            self.typing_test.top200();
+        }
         self.typing_test.duration = Some(config.default_test_duration);
     }
 
@@ -121,9 +152,34 @@ impl TestScreen {
         self.recalc_cursors(gfx_window);
     }
 
+    fn write_journal(&self, entry: storage::JournalEntry) {
+        if let Some(path) = &self.journal_path {
+            if let Err(error) = storage::write_journal(path, &entry) {
+                println!("Error writing the journal: {:?}", error);
+            }
+        }
+    }
+
+    fn auto_save(&mut self, typing_result: &TypingResult, config: &Config) {
+        match storage::open_backend(config).and_then(|mut backend| backend.append(typing_result)) {
+            Err(error) => println!("Error saving results to file: {:?}", error),
+            Ok(()) => {
+                self.auto_saved = true;
+                if let Some(path) = &self.journal_path {
+                    let _ = storage::clear_journal(path);
+                }
+            }
+        }
+    }
+
     fn type_char(&mut self, typed_char: char, gfx_window: &mut GfxWindow) {
         if self.typing_test.typed_char(typed_char) {
             self.typing_state.next_word();
+            if let (Some(journal), Some(typing_result)) =
+                (&self.journal, self.typing_test.result_so_far())
+            {
+                journal.write(typing_result);
+            }
         }
         self.reset_input_label_text(gfx_window);
     }
@@ -290,11 +346,10 @@ impl Screen for TestScreen {
     ) -> Option<Box<dyn Screen>> {
         if self.back_label.ui_state.pressed {
             Some(Box::new(screens::Menu::new(gfx_window)))
-        } else if self.typing_test.ended {
-            let typing_result = self.typing_test.result();
+        } else if let Some(typing_result) = &self.finished_result {
             Some(Box::new(screens::ResultsScreen::new(
-                typing_result,
-                true,
+                typing_result.clone(),
+                !self.auto_saved,
                 gfx_window,
             )))
         } else {
@@ -339,14 +394,23 @@ impl Screen for TestScreen {
     fn mouse_click(&mut self, position: Vector2<f32>) {
         if self.back_label.rect.contains_point(position) {
             self.back_label.ui_state.pressed = true;
+            // leaving a test part way through throws it away
+            self.journal = None;
+            if let Some(path) = &self.journal_path {
+                let _ = storage::clear_journal(path);
+            }
         }
     }
 
+    fn has_unsaved_work(&self) -> bool {
+        self.typing_test.has_started()
+    }
+
     fn update(
         &mut self,
         dt: f32,
         _mouse_position: Vector2<f32>,
-        _config: &Config,
+        config: &Config,
         gfx_window: &mut GfxWindow,
     ) -> bool {
         let mut needs_render = if self.need_font_recalc {
@@ -367,9 +431,18 @@ impl Screen for TestScreen {
             if let Some(true) = self.typing_test.is_done() {
                 println!("Typing test is done!");
                 self.typing_test.end();
+                // waits for the last of the test in progress to be written
+                self.journal = None;
 
-                let typing_result = Some(self.typing_test.result());
+                let typing_result = self.typing_test.result();
                 println!("Result: {:?}", typing_result);
+                if config.auto_save.should_save(&typing_result) {
+                    self.auto_save(&typing_result, config);
+                }
+                if !self.auto_saved {
+                    self.write_journal(storage::JournalEntry::Finished(typing_result.clone()));
+                }
+                self.finished_result = Some(typing_result);
             } else {
                 self.typing_state.update(dt);
                 let skip_num = self.typing_state.skip_num();
@@ -0,0 +1,18 @@
+#version 150 core
+
+in vec2 a_Pos;
+in float a_Alpha;
+out vec4 v_Color;
+
+layout(std140) uniform Locals {
+  vec4 u_Color;
+  vec2 u_WindowDim;
+  float u_Z;
+};
+
+void main() {
+  // the edges fade out over a pixel, which is what antialiases them
+  v_Color = vec4(u_Color.rgb, u_Color.a * a_Alpha);
+  vec2 position = a_Pos / u_WindowDim * 2.0 - 1.0;
+  gl_Position = vec4(position.x, -position.y, u_Z, 1.0);
+}
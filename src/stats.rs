@@ -0,0 +1,641 @@
+use crate::keyboard::{self, Finger};
+use crate::*;
+use chrono::Datelike;
+use std::collections::BTreeMap;
+
+// Figures over a set of results, for `wpm stats`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean_wpm: f64,
+    pub median_wpm: f64,
+    pub best_wpm: i32,
+    pub std_dev_wpm: f64,
+    pub accuracy: f64, // the fraction of words typed correctly
+}
+
+impl Summary {
+    pub fn of(results: &[TypingResult]) -> Option<Self> {
+        if results.is_empty() {
+            return None;
+        }
+        let mut speeds = results
+            .iter()
+            .map(|typing_result| typing_result.wpm)
+            .collect::<Vec<_>>();
+        speeds.sort();
+        let count = speeds.len();
+        let mean_wpm = mean(&speeds);
+        let median_wpm = if count % 2 == 0 {
+            f64::from(speeds[count / 2 - 1] + speeds[count / 2]) / 2.0
+        } else {
+            f64::from(speeds[count / 2])
+        };
+        let variance = speeds
+            .iter()
+            .map(|&wpm| (f64::from(wpm) - mean_wpm).powi(2))
+            .sum::<f64>()
+            / count as f64;
+        let correct_words = results
+            .iter()
+            .map(|typing_result| typing_result.correct_words as i64)
+            .sum::<i64>();
+        let typed_words = results
+            .iter()
+            .map(|typing_result| {
+                (typing_result.correct_words + typing_result.incorrect_words) as i64
+            })
+            .sum::<i64>();
+        Some(Self {
+            count,
+            mean_wpm,
+            median_wpm,
+            best_wpm: speeds[count - 1],
+            std_dev_wpm: variance.sqrt(),
+            accuracy: if typed_words > 0 {
+                correct_words as f64 / typed_words as f64
+            } else {
+                0.0
+            },
+        })
+    }
+}
+
+fn mean(speeds: &[i32]) -> f64 {
+    speeds.iter().map(|&wpm| f64::from(wpm)).sum::<f64>() / speeds.len() as f64
+}
+
+// The average of the latest tests against the ones before them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trend {
+    pub count: usize,
+    pub mean_wpm: f64,
+    pub previous_mean_wpm: Option<f64>, // None until there are enough tests to compare
+}
+
+impl Trend {
+    pub fn change(&self) -> Option<f64> {
+        self.previous_mean_wpm
+            .map(|previous_mean_wpm| self.mean_wpm - previous_mean_wpm)
+    }
+}
+
+// `results` must be oldest first
+pub fn trend(results: &[TypingResult], last: usize) -> Option<Trend> {
+    if results.is_empty() || last == 0 {
+        return None;
+    }
+    let speeds = results
+        .iter()
+        .map(|typing_result| typing_result.wpm)
+        .collect::<Vec<_>>();
+    let split = speeds.len().saturating_sub(last);
+    let (earlier, latest) = speeds.split_at(split);
+    Some(Trend {
+        count: latest.len(),
+        mean_wpm: mean(latest),
+        previous_mean_wpm: if earlier.len() >= last {
+            Some(mean(&earlier[earlier.len() - last..]))
+        } else {
+            None
+        },
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeeklyAverage {
+    pub year: i32, // ISO 8601 week numbering, in the time zone of each test
+    pub week: u32,
+    pub count: usize,
+    pub mean_wpm: f64,
+}
+
+// Oldest week first, weeks without tests are left out
+pub fn weekly_averages(results: &[TypingResult]) -> Vec<WeeklyAverage> {
+    let mut weeks = BTreeMap::new();
+    for typing_result in results {
+        if let Some(datetime) = typing_result.datetime() {
+            let week = datetime.iso_week();
+            weeks
+                .entry((week.year(), week.week()))
+                .or_insert_with(Vec::new)
+                .push(typing_result.wpm);
+        }
+    }
+    weeks
+        .into_iter()
+        .map(|((year, week), speeds)| WeeklyAverage {
+            year,
+            week,
+            count: speeds.len(),
+            mean_wpm: mean(&speeds),
+        })
+        .collect()
+}
+
+// The mean of each result and the `window - 1` before it, or as many as
+// there are. `results` must be oldest first.
+pub fn moving_average(results: &[TypingResult], window: usize) -> Vec<f64> {
+    let speeds = results
+        .iter()
+        .map(|typing_result| typing_result.wpm)
+        .collect::<Vec<_>>();
+    (0..speeds.len())
+        .map(|idx| mean(&speeds[(idx + 1).saturating_sub(window.max(1))..=idx]))
+        .collect()
+}
+
+// Tests are told apart by how long they ran for, to the nearest second.
+// Results from before start times were kept go under None.
+fn test_length_secs(typing_result: &TypingResult) -> Option<u64> {
+    typing_result
+        .duration()
+        .map(|duration| (duration.as_millis() as u64 + 500) / 1000)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PersonalBest {
+    pub duration_secs: Option<u64>,
+    pub typing_result: TypingResult,
+}
+
+// Whether each result was faster than every earlier test of the same length,
+// for marking when bests were set. `results` must be oldest first.
+pub fn set_personal_best(results: &[TypingResult]) -> Vec<bool> {
+    let mut bests = BTreeMap::new();
+    results
+        .iter()
+        .map(|typing_result| {
+            let best = bests
+                .entry(test_length_secs(typing_result))
+                .or_insert(i32::MIN);
+            let is_best = typing_result.wpm > *best;
+            *best = (*best).max(typing_result.wpm);
+            is_best
+        })
+        .collect()
+}
+
+// Shortest tests first, an earlier result keeps the best if it's matched
+pub fn personal_bests(results: &[TypingResult]) -> Vec<PersonalBest> {
+    let mut bests: BTreeMap<Option<u64>, &TypingResult> = BTreeMap::new();
+    for typing_result in results {
+        let best = bests
+            .entry(test_length_secs(typing_result))
+            .or_insert(typing_result);
+        if (
+            typing_result.wpm,
+            std::cmp::Reverse(typing_result.timestamp_ms()),
+        ) > (best.wpm, std::cmp::Reverse(best.timestamp_ms()))
+        {
+            *best = typing_result;
+        }
+    }
+    let mut bests = bests
+        .into_iter()
+        .map(|(duration_secs, typing_result)| PersonalBest {
+            duration_secs,
+            typing_result: typing_result.clone(),
+        })
+        .collect::<Vec<_>>();
+    bests.sort_by_key(|best| best.duration_secs.is_none()); // unknown lengths last
+    bests
+}
+
+// Longer gaps between keys are pauses rather than typing
+const MAX_KEY_INTERVAL_MS: u32 = 2000;
+
+// How often a key, or the keys of one finger, came out wrong and how long it
+// took to get to
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyStats {
+    pub presses: usize,
+    pub errors: usize,
+    pub mean_latency_ms: Option<f64>, // of the right presses, None without any
+}
+
+impl KeyStats {
+    fn of(keystrokes: &[&Keystroke]) -> Self {
+        let latencies = keystrokes
+            .iter()
+            .filter(|keystroke| {
+                !keystroke.is_error()
+                    && keystroke.interval_ms > 0
+                    && keystroke.interval_ms <= MAX_KEY_INTERVAL_MS
+            })
+            .map(|keystroke| f64::from(keystroke.interval_ms))
+            .collect::<Vec<_>>();
+        Self {
+            presses: keystrokes.len(),
+            errors: keystrokes
+                .iter()
+                .filter(|keystroke| keystroke.is_error())
+                .count(),
+            mean_latency_ms: if latencies.is_empty() {
+                None
+            } else {
+                Some(latencies.iter().sum::<f64>() / latencies.len() as f64)
+            },
+        }
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        if self.presses == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.presses as f64
+        }
+    }
+}
+
+// Keystrokes are counted against the key that should have been pressed
+fn grouped_key_stats<K: Ord>(
+    results: &[TypingResult],
+    group_of: impl Fn(char) -> Option<K>,
+) -> BTreeMap<K, KeyStats> {
+    let mut groups = BTreeMap::new();
+    for keystroke in results
+        .iter()
+        .flat_map(|typing_result| &typing_result.keystrokes)
+    {
+        if let Some(group) = keystroke
+            .expected
+            .and_then(|expected| group_of(keyboard::key_for(expected)))
+        {
+            groups.entry(group).or_insert_with(Vec::new).push(keystroke);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(group, keystrokes)| (group, KeyStats::of(&keystrokes)))
+        .collect()
+}
+
+// By key, with shifted characters counted on their key
+pub fn key_stats(results: &[TypingResult]) -> BTreeMap<char, KeyStats> {
+    grouped_key_stats(results, Some)
+}
+
+pub fn finger_stats(results: &[TypingResult]) -> BTreeMap<Finger, KeyStats> {
+    grouped_key_stats(results, keyboard::finger)
+}
+
+// N-grams seen fewer times than this are left out of the slowest and most
+// error prone, one slow transition is more likely a distraction
+pub const MIN_NGRAM_COUNT: usize = 3;
+
+// How a run of letters typed one after the other went, e.g. "th" or "ing"
+#[derive(Clone, Debug, PartialEq)]
+pub struct NgramStats {
+    pub ngram: String,
+    pub count: usize,
+    pub errors: usize,                // times its last letter was typed wrong
+    pub mean_latency_ms: Option<f64>, // from its first key to its last
+}
+
+impl NgramStats {
+    pub fn error_rate(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.count as f64
+        }
+    }
+}
+
+// Every `n` letters in a row within a word, ignoring case. A mistake is
+// counted against the n-gram it ends and starts a new run, since what
+// follows is the correction.
+pub fn ngram_stats(results: &[TypingResult], n: usize) -> Vec<NgramStats> {
+    let mut ngrams: BTreeMap<String, (usize, usize, Vec<f64>)> = BTreeMap::new();
+    for typing_result in results {
+        let mut run: Vec<&Keystroke> = Vec::new();
+        for keystroke in &typing_result.keystrokes {
+            match keystroke.expected {
+                Some(expected) if expected != ' ' => run.push(keystroke),
+                _ => {
+                    run.clear();
+                    continue;
+                }
+            }
+            if n > 0 && run.len() >= n {
+                let window = &run[run.len() - n..];
+                let ngram = window
+                    .iter()
+                    .filter_map(|keystroke| keystroke.expected)
+                    .flat_map(char::to_lowercase)
+                    .collect::<String>();
+                let (count, errors, latencies) = ngrams.entry(ngram).or_default();
+                *count += 1;
+                if keystroke.is_error() {
+                    *errors += 1;
+                } else if window[1..].iter().all(|keystroke| {
+                    keystroke.interval_ms > 0 && keystroke.interval_ms <= MAX_KEY_INTERVAL_MS
+                }) {
+                    latencies.push(
+                        window[1..]
+                            .iter()
+                            .map(|keystroke| f64::from(keystroke.interval_ms))
+                            .sum(),
+                    );
+                }
+            }
+            if keystroke.is_error() {
+                run.clear();
+            }
+        }
+    }
+    ngrams
+        .into_iter()
+        .map(|(ngram, (count, errors, latencies))| NgramStats {
+            ngram,
+            count,
+            errors,
+            mean_latency_ms: if latencies.is_empty() {
+                None
+            } else {
+                Some(latencies.iter().sum::<f64>() / latencies.len() as f64)
+            },
+        })
+        .collect()
+}
+
+// The `count` n-grams that did worst by `weakness`, worst first, out of the
+// ones seen at least `MIN_NGRAM_COUNT` times
+pub fn weakest_ngrams<F>(ngrams: &[NgramStats], count: usize, weakness: F) -> Vec<&NgramStats>
+where
+    F: Fn(&NgramStats) -> Option<f64>,
+{
+    let mut weakest = ngrams
+        .iter()
+        .filter(|ngram| ngram.count >= MIN_NGRAM_COUNT)
+        .filter_map(|ngram| weakness(ngram).map(|value| (value, ngram)))
+        .collect::<Vec<_>>();
+    weakest.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    weakest
+        .into_iter()
+        .take(count)
+        .map(|(_, ngram)| ngram)
+        .collect()
+}
+
+pub fn slowest_ngrams(ngrams: &[NgramStats], count: usize) -> Vec<&NgramStats> {
+    weakest_ngrams(ngrams, count, |ngram| ngram.mean_latency_ms)
+}
+
+// N-grams that were never typed wrong aren't error prone, however few there are
+pub fn most_error_prone_ngrams(ngrams: &[NgramStats], count: usize) -> Vec<&NgramStats> {
+    weakest_ngrams(ngrams, count, |ngram| {
+        if ngram.errors > 0 {
+            Some(ngram.error_rate())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+fn example_result(wpm: i32, ended_at_ms: u64, duration_secs: u64) -> TypingResult {
+    TypingResult {
+        correct_words: wpm,
+        incorrect_words: 1,
+        wpm,
+        time: ended_at_ms / 1000,
+        started_at_ms: ended_at_ms - duration_secs * 1000,
+        ended_at_ms,
+        ..TypingResult::default()
+    }
+}
+
+#[test]
+fn test_summary_of_results() {
+    let results = [40, 50, 60, 70]
+        .iter()
+        .enumerate()
+        .map(|(n, &wpm)| example_result(wpm, 1556223259000 + n as u64 * 60_000, 60))
+        .collect::<Vec<_>>();
+
+    let summary = Summary::of(&results).unwrap();
+
+    assert_eq!(4, summary.count);
+    assert_eq!(55.0, summary.mean_wpm);
+    assert_eq!(55.0, summary.median_wpm);
+    assert_eq!(70, summary.best_wpm);
+    assert!((summary.std_dev_wpm - 125f64.sqrt()).abs() < 1e-9);
+    assert_eq!(220.0 / 224.0, summary.accuracy);
+    assert_eq!(None, Summary::of(&[]));
+}
+
+#[test]
+fn test_trend_compares_the_latest_tests_with_the_ones_before() {
+    let results = [30, 40, 50, 60, 70]
+        .iter()
+        .enumerate()
+        .map(|(n, &wpm)| example_result(wpm, 1556223259000 + n as u64 * 60_000, 60))
+        .collect::<Vec<_>>();
+
+    let latest = trend(&results, 2).unwrap();
+    let not_enough_before = trend(&results, 3).unwrap();
+
+    assert_eq!(2, latest.count);
+    assert_eq!(65.0, latest.mean_wpm);
+    assert_eq!(Some(20.0), latest.change());
+    assert_eq!(None, not_enough_before.change());
+}
+
+#[test]
+fn test_weekly_averages_and_personal_bests() {
+    let day_ms = 24 * 3600 * 1000;
+    let monday_ms = 1556488800000; // 2019-04-28 22:00 UTC, the start of week 18
+    let results = vec![
+        example_result(40, monday_ms - day_ms, 30),
+        example_result(50, monday_ms + day_ms, 60),
+        example_result(70, monday_ms + 2 * day_ms, 60),
+        example_result(60, monday_ms + 3 * day_ms, 30),
+        TypingResult {
+            wpm: 90,
+            time: 1556223259,
+            ..TypingResult::default()
+        },
+    ];
+    let results = results
+        .into_iter()
+        .map(|typing_result| TypingResult {
+            utc_offset_secs: 2 * 3600,
+            ..typing_result
+        })
+        .collect::<Vec<_>>();
+
+    let weeks = weekly_averages(&results[..4]);
+    let bests = personal_bests(&results);
+
+    assert_eq!(
+        vec![
+            WeeklyAverage {
+                year: 2019,
+                week: 17,
+                count: 1,
+                mean_wpm: 40.0
+            },
+            WeeklyAverage {
+                year: 2019,
+                week: 18,
+                count: 3,
+                mean_wpm: 60.0
+            },
+        ],
+        weeks
+    );
+    assert_eq!(
+        vec![Some(30), Some(60), None],
+        bests
+            .iter()
+            .map(|best| best.duration_secs)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![60, 70, 90],
+        bests
+            .iter()
+            .map(|best| best.typing_result.wpm)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_moving_average_and_when_bests_were_set() {
+    let results = [(40, 60), (60, 60), (50, 30), (50, 60), (70, 60)]
+        .iter()
+        .enumerate()
+        .map(|(n, &(wpm, duration_secs))| {
+            example_result(wpm, 1556223259000 + n as u64 * 60_000, duration_secs)
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        vec![40.0, 50.0, 55.0, 50.0, 60.0],
+        moving_average(&results, 2)
+    );
+    assert_eq!(
+        vec![true, true, true, false, true],
+        set_personal_best(&results)
+    );
+}
+
+#[test]
+fn test_key_and_finger_stats() {
+    let keystroke = |expected, typed, interval_ms| Keystroke {
+        expected,
+        typed,
+        interval_ms,
+    };
+    let results = vec![
+        TypingResult {
+            keystrokes: vec![
+                keystroke(Some('A'), 'A', 0),
+                keystroke(Some('s'), 's', 150),
+                keystroke(Some('a'), 's', 90),
+                keystroke(Some('a'), 'a', 250),
+            ],
+            ..TypingResult::default()
+        },
+        TypingResult {
+            keystrokes: vec![
+                keystroke(Some('q'), 'q', 5000), // after a pause
+                keystroke(None, 'x', 100),
+            ],
+            ..TypingResult::default()
+        },
+    ];
+
+    let keys = key_stats(&results);
+    let fingers = finger_stats(&results);
+
+    assert_eq!(
+        vec!['a', 'q', 's'],
+        keys.keys().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        KeyStats {
+            presses: 3,
+            errors: 1,
+            mean_latency_ms: Some(250.0),
+        },
+        keys[&'a']
+    );
+    assert_eq!(None, keys[&'q'].mean_latency_ms);
+    assert_eq!(4, fingers[&Finger::LeftPinky].presses);
+    assert_eq!(0.25, fingers[&Finger::LeftPinky].error_rate());
+    assert_eq!(Some(150.0), fingers[&Finger::LeftRing].mean_latency_ms);
+}
+
+#[test]
+fn test_ngram_latencies_and_errors() {
+    // typed into a real test, with `\u{8}` for backspace and a long pause
+    // before each h so that "th" is clearly the slowest
+    let take_test = |words: &[&str], keys: &str| {
+        let mut typing_test = TypingTest::default();
+        typing_test.set_words(words.iter().map(|&word| String::from(word)).collect());
+        typing_test.duration = Some(std::time::Duration::from_secs(60));
+        for key in keys.chars() {
+            let pause_ms = if key == 'h' { 50 } else { 2 };
+            std::thread::sleep(std::time::Duration::from_millis(pause_ms));
+            if key == '\u{8}' {
+                typing_test.backspace();
+            } else {
+                typing_test.typed_char(key);
+            }
+        }
+        typing_test.end();
+        typing_test.result()
+    };
+    let results = vec![
+        take_test(&["the", "then"], "the then "),
+        // a mistake on the e, then it's retyped
+        take_test(&["then"], "thx\u{8}en "),
+    ];
+
+    let bigrams = ngram_stats(&results, 2);
+    let trigrams = ngram_stats(&results, 3);
+
+    let bigram = |text: &str| bigrams.iter().find(|ngram| ngram.ngram == text).unwrap();
+    assert_eq!(
+        vec!["en", "he", "th"],
+        bigrams
+            .iter()
+            .map(|ngram| ngram.ngram.as_str())
+            .collect::<Vec<_>>()
+    );
+    let th = bigram("th");
+    assert_eq!((3, 0), (th.count, th.errors));
+    assert!(th.mean_latency_ms.unwrap() >= 50.0);
+    let he = bigram("he");
+    assert_eq!((3, 1), (he.count, he.errors));
+    assert!(he.mean_latency_ms.unwrap() >= 2.0);
+    // after the mistake a new run starts at the retyped e
+    let en = bigram("en");
+    assert_eq!((2, 0), (en.count, en.errors));
+    assert_eq!(
+        vec!["hen", "the"],
+        trigrams
+            .iter()
+            .map(|ngram| ngram.ngram.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!((3, 1), (trigrams[1].count, trigrams[1].errors));
+    assert!(trigrams[1].mean_latency_ms.unwrap() >= 52.0);
+    assert_eq!(
+        vec!["th", "he"],
+        slowest_ngrams(&bigrams, 2)
+            .iter()
+            .map(|ngram| ngram.ngram.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["he"],
+        most_error_prone_ngrams(&bigrams, 5)
+            .iter()
+            .map(|ngram| ngram.ngram.as_str())
+            .collect::<Vec<_>>()
+    );
+}
@@ -3,15 +3,44 @@ use dirs::*;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use rmp::*;
-use std::error::Error;
-use std::fs::{DirBuilder, OpenOptions};
-use std::io::{ErrorKind, Read, Write};
-use std::path::PathBuf;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+mod backend;
+pub use backend::{ResultsBackend, ResultsQuery};
+mod backups;
+pub use backups::{backup_results, daily_backup, last_good_backup, list_backups, restore_backup};
+mod encryption;
+pub use encryption::{change_passphrase, is_encrypted_file, set_passphrase};
+mod file_backend;
+pub use file_backend::FileBackend;
+mod file_lock;
+use file_lock::FileLock;
+mod journal;
+pub use journal::{clear_journal, read_journal, write_journal, JournalEntry, JournalWriter};
+mod memory_backend;
+pub use memory_backend::MemoryBackend;
+#[cfg(feature = "sqlite")]
+mod sqlite_backend;
+#[cfg(feature = "sqlite")]
+pub use sqlite_backend::SqliteBackend;
+mod results_index;
+pub use results_index::{load_index, read_indexed_results, IndexEntry, ResultsIndex};
+mod results_reader;
+pub use results_reader::{Record, RecordKind, ResultsReader};
 mod storage_error;
-pub use storage_error::StorageError;
+pub use storage_error::{RecordPosition, StorageError};
 mod storage_v1;
 mod storage_v2;
 mod storage_v3;
+mod storage_v4;
+mod storage_v5;
+mod storage_v6;
+mod storage_v7;
+mod storage_v8;
+mod storage_v9;
+mod verify;
+pub use verify::{repair_results_file, verify_results_file, DamagedRegion, VerifyReport};
 
 #[repr(i8)]
 #[derive(FromPrimitive)]
@@ -19,85 +48,642 @@ enum StorageVersions {
     V1 = 0x01,
     V2 = 0x02,
     V3 = 0x03,
+    V4 = 0x04,
+    V5 = 0x05,
+    V6 = 0x06,
+    V7 = 0x07,
+    V8 = 0x08,
+    V9 = 0x09,
 }
 
-pub const CURRENT_VERSION: i8 = StorageVersions::V3 as i8;
+pub const CURRENT_VERSION: i8 = StorageVersions::V9 as i8;
 
-fn results_path() -> PathBuf {
-    config_dir().unwrap().join("wpm").join("typing_results.wpm")
+// Edits are appended to the log rather than rewriting earlier records, they
+// are folded away when the file is compacted
+#[repr(i8)]
+#[derive(FromPrimitive)]
+enum EditRecords {
+    DeleteResult = 0x40,
+    UpdateNotes = 0x41,
+    ReplaceResult = 0x42,          // a V4 result
+    ReplaceVersionedResult = 0x43, // the version, then a result in that version
+}
+
+pub enum Edit {
+    Delete(u64),
+    UpdateNotes(u64, String),
+    Replace(TypingResult),
+}
+
+const COMPACT_AFTER_EDITS: usize = 32;
+
+pub const MAX_NOTES_LEN: u32 = 1024 * 1024;
+pub const MAX_TAGS: u32 = 256;
+pub const MAX_TAG_LEN: u32 = 1024;
+pub const MAX_TIMELINE_LEN: u32 = 24 * 60 * 60; // a day of seconds
+pub const MAX_KEYSTROKES: u32 = 1024 * 1024;
+pub const MAX_MISSED_WORDS: u32 = 64 * 1024;
+pub const MAX_WORD_LEN: u32 = 1024;
+
+const RESULTS_FILE_NAME: &str = "typing_results.wpm";
+
+pub fn results_path(config: &Config) -> Result<PathBuf, StorageError> {
+    match &config.results_path {
+        Some(path) => Ok(path.clone()),
+        None => Ok(data_dir()
+            .ok_or(StorageError::NoDataDir)?
+            .join("wpm")
+            .join(RESULTS_FILE_NAME)),
+    }
+}
+
+// Results used to be kept in the config dir. Run once at startup, before
+// anything opens the results, to move them to the data dir.
+pub fn migrate_from_config_dir(config: &Config) -> Result<(), StorageError> {
+    if config.results_path.is_some() {
+        return Ok(());
+    }
+    let old_path = match config_dir() {
+        Some(dir) => dir.join("wpm").join(RESULTS_FILE_NAME),
+        None => return Ok(()),
+    };
+    let path = results_path(config)?;
+    if old_path != path {
+        move_results(old_path.as_path(), path.as_path())?;
+    }
+    Ok(())
+}
+
+// Moves the results file along with the lock, index, journal and backups
+// kept next to it, and the SQLite database. Nothing already at the new
+// location is overwritten.
+fn move_results(old_path: &Path, path: &Path) -> Result<(), StorageError> {
+    if old_path.exists() && !path.exists() {
+        let moves = [
+            (old_path.to_path_buf(), path.to_path_buf()),
+            (file_lock::lock_path(old_path), file_lock::lock_path(path)),
+            (
+                results_index::index_path(old_path),
+                results_index::index_path(path),
+            ),
+            (journal::journal_path(old_path), journal::journal_path(path)),
+            (backups::backups_dir(old_path), backups::backups_dir(path)),
+        ];
+        for (old_path, new_path) in &moves {
+            move_path(old_path, new_path)?;
+        }
+    }
+    move_path(
+        old_path.with_extension("sqlite").as_path(),
+        path.with_extension("sqlite").as_path(),
+    )
+}
+
+fn move_path(old_path: &Path, new_path: &Path) -> Result<(), StorageError> {
+    if !old_path.exists() || new_path.exists() {
+        return Ok(());
+    }
+    if let Some(dir_name) = new_path.parent() {
+        DirBuilder::new().recursive(true).create(dir_name)?;
+    }
+    if fs::rename(old_path, new_path).is_ok() {
+        return Ok(());
+    }
+    // rename can't cross filesystems
+    if old_path.is_dir() {
+        DirBuilder::new().recursive(true).create(new_path)?;
+        for entry in fs::read_dir(old_path)? {
+            let entry = entry?;
+            move_path(&entry.path(), &new_path.join(entry.file_name()))?;
+        }
+        fs::remove_dir(old_path)?;
+    } else {
+        fs::copy(old_path, new_path)?;
+        fs::remove_file(old_path)?;
+    }
+    Ok(())
+}
+
+pub fn open_backend(config: &Config) -> Result<Box<dyn ResultsBackend>, StorageError> {
+    let path = results_path(config)?;
+    match config.storage_backend {
+        config::StorageBackend::File => Ok(Box::new(
+            FileBackend::new(path).with_index(config.results_index),
+        )),
+        #[cfg(feature = "sqlite")]
+        config::StorageBackend::Sqlite => {
+            let sqlite_path = path.with_extension("sqlite");
+            let is_new = !sqlite_path.exists();
+            let mut backend = SqliteBackend::open(sqlite_path.as_path())?;
+            // Switching over from the results file brings its results along, once
+            if is_new && path.is_file() {
+                if let Err(error) = merge_results_files(&mut backend, &[path]) {
+                    drop(backend);
+                    let _ = fs::remove_file(sqlite_path.as_path());
+                    return Err(error);
+                }
+            }
+            Ok(Box::new(backend))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        config::StorageBackend::Sqlite => Err(StorageError::NoSqliteSupport),
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct ReadTypingResults {
     pub results: Vec<TypingResult>,
     pub records_need_upgrading: bool, // If older versions were read, we need to save them back
+    pub edit_records: usize,          // Deletes and updates that compaction would fold away
 }
 
-fn read_results<R: Read>(rd: &mut R) -> Result<ReadTypingResults, Box<dyn Error>> {
-    let mut read_typing_results = ReadTypingResults::default();
+impl ReadTypingResults {
+    pub fn needs_compacting(&self) -> bool {
+        self.records_need_upgrading || self.edit_records >= COMPACT_AFTER_EDITS
+    }
 
-    loop {
-        match decode::read_marker(rd) {
-            Err(_) => break,
-            Ok(Marker::FixExt1) => match decode::read_data_i8(rd) {
-                Ok(version_num) => {
-                    if version_num < CURRENT_VERSION {
-                        read_typing_results.records_need_upgrading = true;
-                    }
-                    let typing_result = match FromPrimitive::from_i8(version_num) {
-                        Some(StorageVersions::V1) => Some(storage_v1::StorageV1::read_result(rd)?),
-                        Some(StorageVersions::V2) => Some(storage_v2::StorageV2::read_result(rd)?),
-                        Some(StorageVersions::V3) => Some(storage_v3::StorageV3::read_result(rd)?),
-                        None => None,
-                    };
-                    if let Some(typing_result) = typing_result {
-                        read_typing_results.results.push(typing_result);
-                    }
+    fn apply_edit(&mut self, edit: Edit) {
+        self.edit_records += 1;
+        match edit {
+            Edit::Delete(id) => self.results.retain(|result| result.id != id),
+            Edit::UpdateNotes(id, notes) => {
+                if let Some(result) = self.results.iter_mut().find(|result| result.id == id) {
+                    result.notes = notes;
                 }
-                Err(decode::ValueReadError::InvalidMarkerRead(ref error))
-                    if error.kind() == ErrorKind::UnexpectedEof =>
+            }
+            Edit::Replace(typing_result) => {
+                if let Some(result) = self
+                    .results
+                    .iter_mut()
+                    .find(|result| result.id == typing_result.id)
                 {
-                    break
+                    *result = typing_result;
                 }
-                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+// FNV-1a, carrying on from `hash`
+fn fnv_hash(mut hash: u64, bytes: &[u8]) -> u64 {
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+// Records written before V4 have no ID, so derive one from their contents
+// and their position among the other legacy records. It is written out with
+// the record when it is upgraded, so it stays stable from then on.
+fn legacy_result_id(typing_result: &TypingResult, legacy_idx: usize) -> u64 {
+    let fields = [
+        legacy_idx as u64,
+        typing_result.correct_words as u64,
+        typing_result.incorrect_words as u64,
+        typing_result.backspaces as u64,
+        typing_result.wpm as u64,
+        typing_result.time,
+    ];
+    let mut hash = FNV_OFFSET_BASIS;
+    for field in fields.iter() {
+        hash = fnv_hash(hash, &field.to_le_bytes());
+    }
+    hash = fnv_hash(hash, typing_result.notes.as_bytes());
+    if hash == 0 {
+        1
+    } else {
+        hash
+    }
+}
+
+// Notes are written as their length followed by the string (which has its own length)
+fn read_notes<R: Read>(rd: &mut R) -> Result<String, StorageError> {
+    let notes_len = decode::read_str_len(rd).map_err(StorageError::reading("notes length"))?;
+    check_notes_len(notes_len)?;
+    let str_len = decode::read_str_len(rd).map_err(StorageError::reading("notes"))?;
+    check_notes_len(str_len.max(notes_len))?;
+
+    let mut notes = vec![0; str_len as usize];
+    rd.read_exact(&mut notes)
+        .map_err(|error| StorageError::from_field_io("notes", error))?;
+    String::from_utf8(notes).map_err(|error| StorageError::InvalidText {
+        field: "notes",
+        source: error.utf8_error(),
+        position: None,
+    })
+}
+
+// A corrupted length shouldn't have us allocating gigabytes
+fn check_notes_len(notes_len: u32) -> Result<(), StorageError> {
+    check_len("notes", notes_len, MAX_NOTES_LEN)
+}
+
+fn check_len(field: &'static str, len: u32, limit: u32) -> Result<(), StorageError> {
+    if len > limit {
+        return Err(StorageError::TooLarge {
+            field,
+            len: u64::from(len),
+            limit: u64::from(limit),
+            position: None,
+        });
+    }
+    Ok(())
+}
+
+// How long an array of strings and each string in it can be
+struct StringsLimits {
+    field: &'static str,
+    item_field: &'static str,
+    max_len: u32,
+    max_item_len: u32,
+}
+
+const TAGS_LIMITS: StringsLimits = StringsLimits {
+    field: "tags",
+    item_field: "tag",
+    max_len: MAX_TAGS,
+    max_item_len: MAX_TAG_LEN,
+};
+
+const MISSED_WORDS_LIMITS: StringsLimits = StringsLimits {
+    field: "missed words",
+    item_field: "missed word",
+    max_len: MAX_MISSED_WORDS,
+    max_item_len: MAX_WORD_LEN,
+};
+
+fn check_strings(strings: &[String], limits: &StringsLimits) -> Result<(), StorageError> {
+    check_len(limits.field, strings.len() as u32, limits.max_len)?;
+    for string in strings {
+        check_len(limits.item_field, string.len() as u32, limits.max_item_len)?;
+    }
+    Ok(())
+}
+
+fn write_strings<W: Write>(wr: &mut W, strings: &[String]) -> Result<(), StorageError> {
+    encode::write_array_len(wr, strings.len() as u32)?;
+    for string in strings {
+        encode::write_str(wr, string)?;
+    }
+    Ok(())
+}
+
+fn read_strings<R: Read>(rd: &mut R, limits: &StringsLimits) -> Result<Vec<String>, StorageError> {
+    let strings_len = decode::read_array_len(rd).map_err(StorageError::reading(limits.field))?;
+    check_len(limits.field, strings_len, limits.max_len)?;
+    let mut strings = Vec::with_capacity(strings_len as usize);
+    for _ in 0..strings_len {
+        let string_len =
+            decode::read_str_len(rd).map_err(StorageError::reading(limits.item_field))?;
+        check_len(limits.item_field, string_len, limits.max_item_len)?;
+        let mut string = vec![0; string_len as usize];
+        rd.read_exact(&mut string)
+            .map_err(|error| StorageError::from_field_io(limits.item_field, error))?;
+        strings.push(
+            String::from_utf8(string).map_err(|error| StorageError::InvalidText {
+                field: limits.item_field,
+                source: error.utf8_error(),
+                position: None,
+            })?,
+        );
+    }
+    Ok(strings)
+}
+
+fn check_tags(tags: &[String]) -> Result<(), StorageError> {
+    check_strings(tags, &TAGS_LIMITS)
+}
+
+// Tags are an array of strings
+fn write_tags<W: Write>(wr: &mut W, tags: &[String]) -> Result<(), StorageError> {
+    write_strings(wr, tags)
+}
+
+fn read_tags<R: Read>(rd: &mut R) -> Result<Vec<String>, StorageError> {
+    read_strings(rd, &TAGS_LIMITS)
+}
+
+// Missed words are an array of strings too
+fn write_missed_words<W: Write>(wr: &mut W, words: &[String]) -> Result<(), StorageError> {
+    write_strings(wr, words)
+}
+
+fn read_missed_words<R: Read>(rd: &mut R) -> Result<Vec<String>, StorageError> {
+    read_strings(rd, &MISSED_WORDS_LIMITS)
+}
+
+fn check_timeline(timeline: &[WpmSample]) -> Result<(), StorageError> {
+    check_len("timeline", timeline.len() as u32, MAX_TIMELINE_LEN)
+}
+
+// The timeline is the number of samples, then the raw and net WPM of each
+fn write_timeline<W: Write>(wr: &mut W, timeline: &[WpmSample]) -> Result<(), StorageError> {
+    encode::write_array_len(wr, timeline.len() as u32)?;
+    for sample in timeline {
+        encode::write_f32(wr, sample.raw_wpm)?;
+        encode::write_f32(wr, sample.net_wpm)?;
+    }
+    Ok(())
+}
+
+fn read_timeline<R: Read>(rd: &mut R) -> Result<Vec<WpmSample>, StorageError> {
+    let timeline_len = decode::read_array_len(rd).map_err(StorageError::reading("timeline"))?;
+    check_len("timeline", timeline_len, MAX_TIMELINE_LEN)?;
+    let mut timeline = Vec::with_capacity(timeline_len as usize);
+    for _ in 0..timeline_len {
+        timeline.push(WpmSample {
+            raw_wpm: decode::read_f32(rd).map_err(StorageError::reading("raw wpm"))?,
+            net_wpm: decode::read_f32(rd).map_err(StorageError::reading("net wpm"))?,
+        });
+    }
+    Ok(timeline)
+}
+
+// Keystrokes are the number of them, then the expected and typed
+// characters' code points and the interval of each. Zero stands for no
+// expected character.
+fn write_keystrokes<W: Write>(wr: &mut W, keystrokes: &[Keystroke]) -> Result<(), StorageError> {
+    encode::write_array_len(wr, keystrokes.len() as u32)?;
+    for keystroke in keystrokes {
+        encode::write_u32(wr, keystroke.expected.map_or(0, u32::from))?;
+        encode::write_u32(wr, u32::from(keystroke.typed))?;
+        encode::write_u32(wr, keystroke.interval_ms)?;
+    }
+    Ok(())
+}
+
+fn read_keystrokes<R: Read>(rd: &mut R) -> Result<Vec<Keystroke>, StorageError> {
+    let keystrokes_len = decode::read_array_len(rd).map_err(StorageError::reading("keystrokes"))?;
+    check_len("keystrokes", keystrokes_len, MAX_KEYSTROKES)?;
+    // a damaged code point comes back as U+FFFD rather than losing the result
+    let to_char =
+        |code_point| std::char::from_u32(code_point).unwrap_or(std::char::REPLACEMENT_CHARACTER);
+    let mut keystrokes = Vec::with_capacity(keystrokes_len as usize);
+    for _ in 0..keystrokes_len {
+        let expected = decode::read_u32(rd).map_err(StorageError::reading("expected key"))?;
+        let typed = decode::read_u32(rd).map_err(StorageError::reading("typed key"))?;
+        keystrokes.push(Keystroke {
+            expected: if expected == 0 {
+                None
+            } else {
+                Some(to_char(expected))
             },
-            Ok(_) => {}
+            typed: to_char(typed),
+            interval_ms: decode::read_u32(rd).map_err(StorageError::reading("key interval"))?,
+        });
+    }
+    Ok(keystrokes)
+}
+
+fn read_edit<R: Read>(rd: &mut R, edit_record: EditRecords) -> Result<Edit, StorageError> {
+    match edit_record {
+        EditRecords::DeleteResult => Ok(Edit::Delete(
+            decode::read_u64(rd).map_err(StorageError::reading("id"))?,
+        )),
+        EditRecords::UpdateNotes => {
+            let id = decode::read_u64(rd).map_err(StorageError::reading("id"))?;
+            Ok(Edit::UpdateNotes(id, read_notes(rd)?))
+        }
+        EditRecords::ReplaceResult => Ok(Edit::Replace(storage_v4::StorageV4::read_result(rd)?)),
+        EditRecords::ReplaceVersionedResult => {
+            let version = decode::read_i8(rd).map_err(StorageError::reading("version"))?;
+            read_versioned_result(rd, version)?
+                .map(Edit::Replace)
+                .ok_or(StorageError::UnknownVersion {
+                    version,
+                    position: None,
+                })
+        }
+    }
+}
+
+// None for versions from a newer wpm
+fn read_versioned_result<R: Read>(
+    rd: &mut R,
+    version: i8,
+) -> Result<Option<TypingResult>, StorageError> {
+    let typing_result = match FromPrimitive::from_i8(version) {
+        Some(StorageVersions::V1) => storage_v1::StorageV1::read_result(rd)?,
+        Some(StorageVersions::V2) => storage_v2::StorageV2::read_result(rd)?,
+        Some(StorageVersions::V3) => storage_v3::StorageV3::read_result(rd)?,
+        Some(StorageVersions::V4) => storage_v4::StorageV4::read_result(rd)?,
+        Some(StorageVersions::V5) => storage_v5::StorageV5::read_result(rd)?,
+        Some(StorageVersions::V6) => storage_v6::StorageV6::read_result(rd)?,
+        Some(StorageVersions::V7) => storage_v7::StorageV7::read_result(rd)?,
+        Some(StorageVersions::V8) => storage_v8::StorageV8::read_result(rd)?,
+        Some(StorageVersions::V9) => storage_v9::StorageV9::read_result(rd)?,
+        None => return Ok(None),
+    };
+    Ok(Some(typing_result))
+}
+
+fn read_results<R: Read>(rd: &mut R) -> Result<ReadTypingResults, StorageError> {
+    let mut read_typing_results = ReadTypingResults::default();
+    for record in ResultsReader::new(rd) {
+        match record?.kind {
+            RecordKind::Result {
+                version,
+                typing_result,
+            } => {
+                if version < CURRENT_VERSION {
+                    read_typing_results.records_need_upgrading = true;
+                }
+                read_typing_results.results.push(typing_result);
+            }
+            RecordKind::Edit(edit) => read_typing_results.apply_edit(edit),
+            // versions from the future are skipped
+            RecordKind::Unknown { .. } => {}
         }
     }
     Ok(read_typing_results)
 }
 
-pub fn read_results_from_file() -> Result<ReadTypingResults, Box<dyn Error>> {
-    match OpenOptions::new().read(true).open(results_path().as_path()) {
+fn read_results_from_path_unlocked(path: &Path) -> Result<ReadTypingResults, StorageError> {
+    match OpenOptions::new().read(true).open(path) {
         Err(ref error) if error.kind() == ErrorKind::NotFound => Ok(ReadTypingResults::default()),
         Err(error) => Err(error.into()),
-        Ok(mut fd) => read_results(&mut fd),
+        Ok(mut fd) => {
+            let mut header = Vec::new();
+            (&mut fd).take(8).read_to_end(&mut header)?;
+            if encryption::is_encrypted(&header) {
+                fd.read_to_end(&mut header)?;
+                let plain = encryption::plain_contents(path, header)?;
+                read_results(&mut &plain[..])
+            } else {
+                read_results(&mut (&header[..]).chain(BufReader::new(fd)))
+            }
+        }
     }
 }
 
-fn save_result<W: Write>(wr: &mut W, typing_result: &TypingResult) -> Result<(), Box<dyn Error>> {
+pub fn read_results_from_path(path: &Path) -> Result<ReadTypingResults, StorageError> {
+    let _lock = FileLock::shared(path)?;
+    read_results_from_path_unlocked(path)
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct MergeSummary {
+    pub added: usize,
+    pub duplicates: usize,
+}
+
+// Adds the results from other results files (of any version, e.g. from
+// another machine) that the backend doesn't already have
+pub fn merge_results_files(
+    backend: &mut dyn ResultsBackend,
+    paths: &[PathBuf],
+) -> Result<MergeSummary, StorageError> {
+    let mut merged = backend.list()?;
+    let mut summary = MergeSummary::default();
+    for path in paths {
+        if !path.is_file() {
+            let message = format!("No results file at {}", path.display());
+            return Err(std::io::Error::new(ErrorKind::NotFound, message).into());
+        }
+        let read_typing_results = read_results_from_path(path.as_path())?;
+        let (unseen, duplicates) = unseen_results(&merged, read_typing_results.results);
+        for typing_result in &unseen {
+            backend.append(typing_result)?;
+        }
+        summary.added += unseen.len();
+        summary.duplicates += duplicates;
+        merged.extend(unseen);
+    }
+    Ok(summary)
+}
+
+pub fn read_results_from_file(config: &Config) -> Result<ReadTypingResults, StorageError> {
+    read_results_from_path(results_path(config)?.as_path())
+}
+
+fn save_result<W: Write>(wr: &mut W, typing_result: &TypingResult) -> Result<(), StorageError> {
+    check_notes_len(typing_result.notes.len() as u32)?;
+    check_tags(&typing_result.tags)?;
+    check_timeline(&typing_result.timeline)?;
+    check_len(
+        "keystrokes",
+        typing_result.keystrokes.len() as u32,
+        MAX_KEYSTROKES,
+    )?;
+    check_strings(&typing_result.missed_words, &MISSED_WORDS_LIMITS)?;
     encode::write_ext_meta(wr, 1, CURRENT_VERSION)?;
-    storage_v3::StorageV3::save_result(wr, typing_result)
+    storage_v9::StorageV9::save_result(wr, typing_result)
 }
 
-pub fn save_result_to_file(typing_result: &TypingResult) -> Result<(), Box<dyn Error>> {
-    if let Some(dir_name) = results_path().parent() {
-        DirBuilder::new().recursive(true).create(dir_name)?;
+fn save_delete<W: Write>(wr: &mut W, id: u64) -> Result<(), StorageError> {
+    encode::write_ext_meta(wr, 1, EditRecords::DeleteResult as i8)?;
+    encode::write_u64(wr, id)?;
+    Ok(())
+}
+
+#[cfg(test)]
+fn save_notes_update<W: Write>(wr: &mut W, id: u64, notes: &str) -> Result<(), StorageError> {
+    encode::write_ext_meta(wr, 1, EditRecords::UpdateNotes as i8)?;
+    encode::write_u64(wr, id)?;
+    encode::write_str_len(wr, notes.len() as u32)?;
+    encode::write_str(wr, notes)?;
+    Ok(())
+}
+
+fn save_replace<W: Write>(wr: &mut W, typing_result: &TypingResult) -> Result<(), StorageError> {
+    check_notes_len(typing_result.notes.len() as u32)?;
+    check_tags(&typing_result.tags)?;
+    check_timeline(&typing_result.timeline)?;
+    check_len(
+        "keystrokes",
+        typing_result.keystrokes.len() as u32,
+        MAX_KEYSTROKES,
+    )?;
+    check_strings(&typing_result.missed_words, &MISSED_WORDS_LIMITS)?;
+    encode::write_ext_meta(wr, 1, EditRecords::ReplaceVersionedResult as i8)?;
+    encode::write_i8(wr, CURRENT_VERSION)?;
+    storage_v9::StorageV9::save_result(wr, typing_result)
+}
+
+// The whole record is encoded up front and appended in a single write, so
+// another process reading the file sees all of it or none of it
+fn append_record<F>(path: &Path, encode_record: F) -> Result<(), StorageError>
+where
+    F: FnOnce(&mut Vec<u8>) -> Result<(), StorageError>,
+{
+    let mut buffer = Vec::new();
+    encode_record(&mut buffer)?;
+
+    let _lock = FileLock::exclusive(path)?;
+    let contents = encryption::contents_to_append(path, &buffer)?;
+    let mut fd = OpenOptions::new().create(true).append(true).open(path)?;
+    fd.write_all(&contents)?;
+    Ok(())
+}
+
+pub fn save_result_to_file(
+    config: &Config,
+    typing_result: &TypingResult,
+) -> Result<(), StorageError> {
+    append_record(results_path(config)?.as_path(), |wr| {
+        save_result(wr, typing_result)
+    })
+}
+
+// Replace the file with just these results, all in the current version
+fn write_results_to_path_unlocked(
+    path: &Path,
+    results: &[TypingResult],
+) -> Result<(), StorageError> {
+    let mut buffer = Vec::new();
+    for typing_result in results {
+        save_result(&mut buffer, typing_result)?;
+    }
+    let encrypted = encryption::is_encrypted_file(path)?;
+    write_file_atomically(
+        path,
+        &encryption::contents_to_write(path, &buffer, encrypted)?,
+    )?;
+    results_index::remove_index(path)
+}
+
+// Write to a temporary file then rename it over the top, so readers only ever
+// see the old or new contents
+fn write_file_atomically(path: &Path, contents: &[u8]) -> Result<(), StorageError> {
+    replace_file(path, contents, true)
+}
+
+// Without waiting for the disk unless `sync`, for files that are rewritten
+// often and only a safety net
+fn replace_file(path: &Path, contents: &[u8], sync: bool) -> Result<(), StorageError> {
+    let mut tmp_file_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_file_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_file_name);
+    {
+        let mut fd = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(tmp_path.as_path())?;
+        fd.write_all(contents)?;
+        if sync {
+            fd.sync_all()?;
+        }
     }
-    let mut fd = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .append(true)
-        .open(results_path().as_path())?;
-    save_result(&mut fd, typing_result)
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+// Rewrite the file with only the live results, holding the lock throughout
+// so that nothing appended in the meantime gets lost
+fn compact_results_at_path(path: &Path) -> Result<(), StorageError> {
+    let _lock = FileLock::exclusive(path)?;
+    let read_typing_results = read_results_from_path_unlocked(path)?;
+    write_results_to_path_unlocked(path, &read_typing_results.results)
 }
 
 pub trait Storage {
-    fn save_result<W: Write>(
-        wr: &mut W,
-        typing_result: &TypingResult,
-    ) -> Result<(), Box<dyn Error>>;
-    fn read_result<R: Read>(rd: &mut R) -> Result<TypingResult, Box<dyn Error>>;
+    fn save_result<W: Write>(wr: &mut W, typing_result: &TypingResult) -> Result<(), StorageError>;
+    fn read_result<R: Read>(rd: &mut R) -> Result<TypingResult, StorageError>;
 }
 
 #[test]
@@ -128,7 +714,13 @@ fn test_write_then_read_back_v1_records_only() {
     let read_typing_results = read_results(&mut &buffer[..]).expect("Read back the results");
 
     assert_eq!(1, read_typing_results.results.len());
-    assert_eq!(typing_result, read_typing_results.results[0]);
+    assert_eq!(
+        TypingResult {
+            id: legacy_result_id(&typing_result, 0),
+            ..typing_result
+        },
+        read_typing_results.results[0]
+    );
     assert_eq!(true, read_typing_results.records_need_upgrading);
 }
 
@@ -151,7 +743,13 @@ fn test_write_then_read_back_v2_records_only() {
     let read_typing_results = read_results(&mut &buffer[..]).expect("Read back the results");
 
     assert_eq!(1, read_typing_results.results.len());
-    assert_eq!(typing_result, read_typing_results.results[0]);
+    assert_eq!(
+        TypingResult {
+            id: legacy_result_id(&typing_result, 0),
+            ..typing_result
+        },
+        read_typing_results.results[0]
+    );
     assert_eq!(true, read_typing_results.records_need_upgrading);
 }
 
@@ -175,13 +773,20 @@ fn test_write_then_read_back_v3_records_only() {
     let read_typing_results = read_results(&mut &buffer[..]).expect("Read back the results");
 
     assert_eq!(1, read_typing_results.results.len());
-    assert_eq!(typing_result, read_typing_results.results[0]);
-    assert_eq!(false, read_typing_results.records_need_upgrading);
+    assert_eq!(
+        TypingResult {
+            id: legacy_result_id(&typing_result, 0),
+            ..typing_result
+        },
+        read_typing_results.results[0]
+    );
+    assert_eq!(true, read_typing_results.records_need_upgrading);
 }
 
 #[test]
 fn test_read_and_write_current_version() {
     let typing_result = TypingResult {
+        id: 42,
         correct_words: 102,
         incorrect_words: 5,
         backspaces: 2,
@@ -277,14 +882,278 @@ fn test_write_some_v1s_and_v2s_and_read_them_back() {
     let read_typing_results = read_results(&mut &buffer[..]).expect("Read back the results");
 
     assert_eq!(4, read_typing_results.results.len());
+    let expected_results = vec![
+        typing_result1,
+        typing_result2,
+        typing_result3,
+        typing_result4,
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(legacy_idx, typing_result)| TypingResult {
+        id: legacy_result_id(&typing_result, legacy_idx),
+        ..typing_result
+    })
+    .collect::<Vec<_>>();
+    assert_eq!(expected_results, read_typing_results.results);
+    assert_eq!(true, read_typing_results.records_need_upgrading);
+}
+
+#[test]
+fn test_legacy_ids_are_kept_when_records_are_upgraded() {
+    let typing_result = TypingResult {
+        correct_words: 87,
+        incorrect_words: 3,
+        backspaces: 2,
+        wpm: 87,
+        time: 1556223259,
+        notes: String::from("Old notes"),
+        ..TypingResult::default()
+    };
+
+    let mut buffer = Vec::new();
+
+    encode::write_ext_meta(&mut buffer, 1, StorageVersions::V3 as i8).unwrap();
+    storage_v3::StorageV3::save_result(&mut buffer, &typing_result).unwrap();
+    encode::write_ext_meta(&mut buffer, 1, StorageVersions::V3 as i8).unwrap();
+    storage_v3::StorageV3::save_result(&mut buffer, &typing_result).unwrap();
+
+    let legacy_results = read_results(&mut &buffer[..]).expect("Read back the results");
+    assert_ne!(0, legacy_results.results[0].id);
+    assert_ne!(legacy_results.results[0].id, legacy_results.results[1].id);
+
+    let mut upgraded_buffer = Vec::new();
+    for typing_result in &legacy_results.results {
+        save_result(&mut upgraded_buffer, typing_result).unwrap();
+    }
+
+    let upgraded_results = read_results(&mut &upgraded_buffer[..]).expect("Read back the results");
+
+    assert_eq!(legacy_results.results, upgraded_results.results);
+    assert_eq!(false, upgraded_results.records_need_upgrading);
+}
+
+#[test]
+fn test_delete_and_update_notes_records_are_applied_when_read_back() {
+    let typing_result1 = TypingResult {
+        id: 1,
+        correct_words: 50,
+        wpm: 50,
+        time: 1556223259,
+        ..TypingResult::default()
+    };
+    let typing_result2 = TypingResult {
+        id: 2,
+        correct_words: 60,
+        wpm: 60,
+        time: 1556223359,
+        notes: String::from("Before"),
+        ..TypingResult::default()
+    };
+
+    let mut buffer = Vec::new();
+
+    save_result(&mut buffer, &typing_result1).unwrap();
+    save_result(&mut buffer, &typing_result2).unwrap();
+    save_notes_update(&mut buffer, 2, "After").unwrap();
+    save_delete(&mut buffer, 1).unwrap();
+
+    let read_typing_results = read_results(&mut &buffer[..]).expect("Read back the results");
+
     assert_eq!(
-        vec![
-            typing_result1,
-            typing_result2,
-            typing_result3,
-            typing_result4
-        ],
+        vec![TypingResult {
+            notes: String::from("After"),
+            ..typing_result2
+        }],
         read_typing_results.results
     );
-    assert_eq!(true, read_typing_results.records_need_upgrading);
+    assert_eq!(2, read_typing_results.edit_records);
+    assert_eq!(false, read_typing_results.needs_compacting());
+}
+
+#[test]
+fn test_many_edits_mean_the_results_need_compacting() {
+    let typing_result = TypingResult {
+        id: 7,
+        ..TypingResult::default()
+    };
+
+    let mut buffer = Vec::new();
+
+    save_result(&mut buffer, &typing_result).unwrap();
+    for edit in 0..COMPACT_AFTER_EDITS {
+        save_notes_update(&mut buffer, 7, &format!("Edit {}", edit)).unwrap();
+    }
+
+    let read_typing_results = read_results(&mut &buffer[..]).expect("Read back the results");
+
+    assert_eq!(1, read_typing_results.results.len());
+    assert_eq!(
+        format!("Edit {}", COMPACT_AFTER_EDITS - 1),
+        read_typing_results.results[0].notes
+    );
+    assert_eq!(true, read_typing_results.needs_compacting());
+}
+
+#[test]
+fn test_concurrent_appends_do_not_interleave() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let writers = (0..4)
+        .map(|writer| {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                for n in 0..50 {
+                    let typing_result = TypingResult {
+                        id: writer * 1000 + n + 1,
+                        notes: "x".repeat(n as usize * 20),
+                        ..TypingResult::default()
+                    };
+                    append_record(path.as_path(), |wr| save_result(wr, &typing_result))
+                        .expect("append to work");
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let read_typing_results = read_results_from_path(path.as_path());
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+
+    assert_eq!(200, read_typing_results.unwrap().results.len());
+}
+
+#[test]
+fn test_merge_results_files_skips_duplicates() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let laptop_result = TypingResult {
+        correct_words: 70,
+        wpm: 70,
+        time: 1556223259,
+        ..TypingResult::default()
+    };
+    let desktop_result = TypingResult {
+        id: 2,
+        correct_words: 80,
+        wpm: 80,
+        time: 1556229999,
+        ..TypingResult::default()
+    };
+
+    let mut buffer = Vec::new();
+    // an old record, and the same test again with an ID
+    encode::write_ext_meta(&mut buffer, 1, StorageVersions::V2 as i8).unwrap();
+    storage_v2::StorageV2::save_result(&mut buffer, &laptop_result).unwrap();
+    save_result(
+        &mut buffer,
+        &TypingResult {
+            id: 1,
+            ..laptop_result.clone()
+        },
+    )
+    .unwrap();
+    save_result(&mut buffer, &desktop_result).unwrap();
+    fs::write(path.as_path(), &buffer).unwrap();
+
+    let mut backend = MemoryBackend::with_results(vec![desktop_result]);
+    let summary = merge_results_files(&mut backend, std::slice::from_ref(&path));
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+
+    assert_eq!(
+        MergeSummary {
+            added: 1,
+            duplicates: 2
+        },
+        summary.unwrap()
+    );
+    let merged = backend.list().unwrap();
+    assert_eq!(2, merged.len());
+    assert_eq!(laptop_result.time, merged[1].time);
+    assert_ne!(0, merged[1].id);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_switching_to_sqlite_imports_the_results_file() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let typing_result = TypingResult {
+        id: 1,
+        correct_words: 70,
+        wpm: 70,
+        time: 1556223259,
+        ..TypingResult::default()
+    };
+    let mut buffer = Vec::new();
+    save_result(&mut buffer, &typing_result).unwrap();
+    fs::write(path.as_path(), &buffer).unwrap();
+
+    let config = Config {
+        default_test_duration: std::time::Duration::from_secs(60),
+        storage_backend: config::StorageBackend::Sqlite,
+        results_path: Some(path.clone()),
+        backups_to_keep: 0,
+        results_index: false,
+        auto_save: config::AutoSave::default(),
+    };
+    let imported = open_backend(&config).and_then(|mut backend| backend.list());
+    // the results file isn't read again once the database exists
+    fs::write(path.as_path(), b"").unwrap();
+    let reopened = open_backend(&config).and_then(|mut backend| backend.list());
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+    let _ = fs::remove_file(path.with_extension("sqlite"));
+
+    assert_eq!(vec![typing_result.clone()], imported.unwrap());
+    assert_eq!(vec![typing_result], reopened.unwrap());
+}
+
+#[test]
+fn test_moving_results_brings_the_files_kept_next_to_them() {
+    let dir = std::env::temp_dir().join(format!("wpm-test-{}", new_result_id()));
+    let old_path = dir.join("config").join(RESULTS_FILE_NAME);
+    let path = dir.join("data").join(RESULTS_FILE_NAME);
+    let backup = backups::backups_dir(old_path.as_path()).join("typing_results-2019-04-25.wpm");
+    fs::create_dir_all(backup.parent().unwrap()).unwrap();
+    for file in &[
+        old_path.clone(),
+        results_index::index_path(old_path.as_path()),
+        journal::journal_path(old_path.as_path()),
+        backup.clone(),
+    ] {
+        fs::write(file, file.to_string_lossy().as_bytes()).unwrap();
+    }
+
+    let moved = move_results(old_path.as_path(), path.as_path());
+    let new_backup = backups::backups_dir(path.as_path()).join("typing_results-2019-04-25.wpm");
+    let contents = [
+        fs::read_to_string(path.as_path()).ok(),
+        fs::read_to_string(results_index::index_path(path.as_path())).ok(),
+        fs::read_to_string(journal::journal_path(path.as_path())).ok(),
+        fs::read_to_string(new_backup).ok(),
+    ];
+    let left_behind = old_path.exists() || backup.exists();
+    let _ = fs::remove_dir_all(dir);
+
+    moved.unwrap();
+    assert_eq!(
+        [
+            Some(old_path.to_string_lossy().into_owned()),
+            Some(
+                results_index::index_path(old_path.as_path())
+                    .to_string_lossy()
+                    .into_owned()
+            ),
+            Some(
+                journal::journal_path(old_path.as_path())
+                    .to_string_lossy()
+                    .into_owned()
+            ),
+            Some(backup.to_string_lossy().into_owned()),
+        ],
+        contents
+    );
+    assert!(!left_behind);
 }
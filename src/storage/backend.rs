@@ -0,0 +1,98 @@
+use crate::storage::StorageError;
+use crate::{normalise_tag, TypingResult};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResultsQuery {
+    pub since: Option<u64>, // seconds since the epoch, inclusive
+    pub until: Option<u64>, // seconds since the epoch, exclusive
+    pub min_wpm: Option<i32>,
+    pub limit: Option<usize>,
+    pub skip: usize, // for paging through results, applied before the limit
+    pub tag: Option<String>,
+}
+
+impl ResultsQuery {
+    pub fn matches(&self, typing_result: &TypingResult) -> bool {
+        self.matches_fields(typing_result.time, typing_result.wpm, &typing_result.tags)
+    }
+
+    // For matching what the index keeps without making a whole result
+    pub(crate) fn matches_fields(&self, time: u64, wpm: i32, tags: &[String]) -> bool {
+        self.since.is_none_or(|since| time >= since)
+            && self.until.is_none_or(|until| time < until)
+            && self.min_wpm.is_none_or(|min_wpm| wpm >= min_wpm)
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| tags.contains(&normalise_tag(tag)))
+    }
+
+    // Newest results first, so a limit keeps the most recent ones
+    pub fn apply(&self, results: Vec<TypingResult>) -> Vec<TypingResult> {
+        let mut matching = results
+            .into_iter()
+            .filter(|typing_result| self.matches(typing_result))
+            .collect::<Vec<_>>();
+        matching.sort_by_key(|typing_result| std::cmp::Reverse(typing_result.timestamp_ms()));
+        matching.drain(..self.skip.min(matching.len()));
+        if let Some(limit) = self.limit {
+            matching.truncate(limit);
+        }
+        matching
+    }
+}
+
+pub trait ResultsBackend {
+    fn list(&mut self) -> Result<Vec<TypingResult>, StorageError>;
+    fn append(&mut self, typing_result: &TypingResult) -> Result<(), StorageError>;
+    // Replaces the stored result with the same ID
+    fn update(&mut self, typing_result: &TypingResult) -> Result<(), StorageError>;
+    fn delete(&mut self, id: u64) -> Result<(), StorageError>;
+    fn query(&mut self, query: &ResultsQuery) -> Result<Vec<TypingResult>, StorageError> {
+        Ok(query.apply(self.list()?))
+    }
+    // Maintenance that reads never do as a side effect, e.g. rewriting old
+    // records. Nothing to do for most backends.
+    fn compact(&mut self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_query_filters_sorts_and_limits_results() {
+    let results = (1..=5)
+        .map(|n| TypingResult {
+            id: n,
+            wpm: 40 + n as i32 * 10,
+            time: 1556223259 + n * 60,
+            ..TypingResult::default()
+        })
+        .collect::<Vec<_>>();
+
+    let query = ResultsQuery {
+        since: Some(1556223259 + 2 * 60),
+        until: Some(1556223259 + 5 * 60),
+        min_wpm: Some(70),
+        limit: Some(1),
+        skip: 0,
+        tag: None,
+    };
+
+    let matching = query.apply(results.clone());
+
+    assert_eq!(vec![4], matching.iter().map(|r| r.id).collect::<Vec<_>>());
+
+    let second_page = ResultsQuery {
+        limit: Some(2),
+        skip: 2,
+        ..ResultsQuery::default()
+    };
+    assert_eq!(
+        vec![3, 2],
+        second_page
+            .apply(results)
+            .iter()
+            .map(|r| r.id)
+            .collect::<Vec<_>>()
+    );
+}
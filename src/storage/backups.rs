@@ -0,0 +1,202 @@
+use crate::storage::*;
+use chrono::prelude::{Local, NaiveDate};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Backups are copies of the results file named by the day they were taken,
+// e.g. typing_results.wpm.backups/typing_results-2019-04-25.wpm, so sorting
+// by name sorts by age. Each results file has its own directory of them.
+pub(crate) fn backups_dir(path: &Path) -> PathBuf {
+    let mut dir_name = path.file_name().unwrap_or_default().to_os_string();
+    dir_name.push(".backups");
+    path.with_file_name(dir_name)
+}
+
+fn backup_name_parts(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("typing_results"));
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("wpm"));
+    (stem, extension)
+}
+
+fn backup_path(path: &Path, date: &str) -> PathBuf {
+    let (stem, extension) = backup_name_parts(path);
+    backups_dir(path).join(format!("{}-{}.{}", stem, date, extension))
+}
+
+fn today() -> String {
+    Local::now().format(DATE_FORMAT).to_string()
+}
+
+// Newest first
+pub fn list_backups(path: &Path) -> Result<Vec<PathBuf>, StorageError> {
+    let (stem, extension) = backup_name_parts(path);
+    let prefix = format!("{}-", stem);
+    let suffix = format!(".{}", extension);
+    let entries = match fs::read_dir(backups_dir(path)) {
+        Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        entries => entries?,
+    };
+    let mut backups = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        // anything else put in the directory is left alone
+        let date = file_name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(&suffix));
+        if date.is_some_and(|date| NaiveDate::parse_from_str(date, DATE_FORMAT).is_ok()) {
+            backups.push(entry.path());
+        }
+    }
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+fn backup_results_as(
+    path: &Path,
+    backup: &Path,
+    backups_to_keep: usize,
+) -> Result<(), StorageError> {
+    let contents = {
+        let _lock = FileLock::shared(path)?;
+        fs::read(path)?
+    };
+    DirBuilder::new()
+        .recursive(true)
+        .create(backups_dir(path))?;
+    write_file_atomically(backup, &contents)?;
+
+    for old_backup in list_backups(path)?.iter().skip(backups_to_keep.max(1)) {
+        fs::remove_file(old_backup)?;
+    }
+    Ok(())
+}
+
+// Takes today's backup now, replacing any taken earlier today
+pub fn backup_results(path: &Path, backups_to_keep: usize) -> Result<PathBuf, StorageError> {
+    let backup = backup_path(path, &today());
+    backup_results_as(path, backup.as_path(), backups_to_keep)?;
+    Ok(backup)
+}
+
+// Takes a backup if there isn't one for today yet
+pub fn daily_backup(path: &Path, backups_to_keep: usize) -> Result<Option<PathBuf>, StorageError> {
+    let backup = backup_path(path, &today());
+    if backups_to_keep == 0 || backup.exists() || !path.exists() {
+        return Ok(None);
+    }
+    backup_results_as(path, backup.as_path(), backups_to_keep)?;
+    Ok(Some(backup))
+}
+
+// Backups are encrypted with the passphrase of the results file they came
+// from, and re-encrypted along with it when the passphrase changes
+fn read_backup(path: &Path, backup: &Path) -> Result<ReadTypingResults, StorageError> {
+    let plain = encryption::plain_contents(path, fs::read(backup)?)?;
+    read_results(&mut &plain[..])
+}
+
+// The newest backup that can be read from start to finish
+pub fn last_good_backup(path: &Path) -> Result<Option<PathBuf>, StorageError> {
+    Ok(list_backups(path)?
+        .into_iter()
+        .find(|backup| read_backup(path, backup).is_ok()))
+}
+
+pub fn restore_backup(path: &Path, backup: &Path) -> Result<(), StorageError> {
+    read_backup(path, backup)?;
+    let contents = fs::read(backup)?;
+    let _lock = FileLock::exclusive(path)?;
+    write_file_atomically(path, &contents)?;
+    results_index::remove_index(path)
+}
+
+#[test]
+fn test_old_backups_are_pruned_and_broken_ones_skipped_when_restoring() {
+    let dir = std::env::temp_dir().join(format!("wpm-test-{}", new_result_id()));
+    let path = dir.join("typing_results.wpm");
+    let typing_result = TypingResult {
+        id: 1,
+        wpm: 50,
+        time: 1556223259,
+        ..TypingResult::default()
+    };
+    let mut buffer = Vec::new();
+    save_result(&mut buffer, &typing_result).unwrap();
+    DirBuilder::new().recursive(true).create(&dir).unwrap();
+    fs::write(path.as_path(), &buffer).unwrap();
+
+    for date in &["2019-04-23", "2019-04-24", "2019-04-25"] {
+        backup_results_as(path.as_path(), &backup_path(path.as_path(), date), 2).unwrap();
+    }
+    let backups = list_backups(path.as_path()).unwrap();
+    // the newest backup is cut short, as if we crashed while it was written
+    fs::write(backups[0].as_path(), &buffer[..buffer.len() - 4]).unwrap();
+    fs::write(path.as_path(), &buffer[..2]).unwrap();
+
+    let good_backup = last_good_backup(path.as_path()).unwrap();
+    let restored = good_backup
+        .as_ref()
+        .map(|backup| restore_backup(path.as_path(), backup));
+    let read_typing_results = read_results_from_path(path.as_path());
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(
+        vec![
+            backup_path(path.as_path(), "2019-04-25"),
+            backup_path(path.as_path(), "2019-04-24")
+        ],
+        backups
+    );
+    assert_eq!(Some(backups[1].clone()), good_backup);
+    assert!(restored.unwrap().is_ok());
+    assert_eq!(vec![typing_result], read_typing_results.unwrap().results);
+}
+
+#[test]
+fn test_backups_of_results_files_in_the_same_dir_are_kept_apart() {
+    let dir = std::env::temp_dir().join(format!("wpm-test-{}", new_result_id()));
+    let work = dir.join("work.wpm");
+    let work_laptop = dir.join("work-laptop.wpm");
+    DirBuilder::new().recursive(true).create(&dir).unwrap();
+    fs::write(work.as_path(), b"work").unwrap();
+    fs::write(work_laptop.as_path(), b"work-laptop").unwrap();
+
+    backup_results_as(
+        work.as_path(),
+        &backup_path(work.as_path(), "2019-04-24"),
+        1,
+    )
+    .unwrap();
+    for date in &["2019-04-25", "2019-04-26"] {
+        backup_results_as(
+            work_laptop.as_path(),
+            &backup_path(work_laptop.as_path(), date),
+            1,
+        )
+        .unwrap();
+    }
+    // a stray copy of the other file's backup isn't taken for one of ours
+    let stray = backups_dir(work.as_path()).join("work-laptop-2019-04-27.wpm");
+    fs::write(stray.as_path(), b"work-laptop").unwrap();
+
+    let work_backups = list_backups(work.as_path());
+    let work_laptop_backups = list_backups(work_laptop.as_path());
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(
+        vec![backup_path(work.as_path(), "2019-04-24")],
+        work_backups.unwrap()
+    );
+    assert_eq!(
+        vec![backup_path(work_laptop.as_path(), "2019-04-26")],
+        work_laptop_backups.unwrap()
+    );
+}
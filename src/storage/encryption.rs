@@ -0,0 +1,464 @@
+use crate::storage::*;
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// An encrypted results file starts with this, then the salt for deriving its
+// key from the passphrase. The records follow as frames, each one the length
+// of its ciphertext, a nonce and the ciphertext, so appends stay appends.
+const MAGIC: &[u8; 8] = b"WPMENC01";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN;
+// Each rewrite of a file brings a new salt, so old keys are dropped in time
+const MAX_CACHED_KEYS: usize = 8;
+
+type Salt = [u8; SALT_LEN];
+
+struct Passphrase {
+    passphrase: String,
+    keys: HashMap<Salt, [u8; KEY_LEN]>, // deriving a key is slow on purpose
+}
+
+impl Passphrase {
+    fn new(passphrase: String) -> Self {
+        Self {
+            passphrase,
+            keys: HashMap::new(),
+        }
+    }
+
+    fn cipher(&mut self, salt: &Salt) -> Result<XChaCha20Poly1305, StorageError> {
+        if !self.keys.contains_key(salt) {
+            if self.keys.len() >= MAX_CACHED_KEYS {
+                self.keys.clear();
+            }
+            let mut key = [0; KEY_LEN];
+            Argon2::default()
+                .hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+                .map_err(|err| StorageError::Encryption(err.to_string()))?;
+            self.keys.insert(*salt, key);
+        }
+        Ok(XChaCha20Poly1305::new(Key::from_slice(&self.keys[salt])))
+    }
+}
+
+// Passphrases by results file, set once at startup by whoever asked for it
+static PASSPHRASES: Mutex<Option<HashMap<PathBuf, Passphrase>>> = Mutex::new(None);
+
+pub fn set_passphrase(path: &Path, passphrase: Option<String>) {
+    use_passphrase(path, passphrase.map(Passphrase::new));
+}
+
+fn use_passphrase(path: &Path, passphrase: Option<Passphrase>) {
+    let mut passphrases = PASSPHRASES.lock().unwrap_or_else(|err| err.into_inner());
+    let passphrases = passphrases.get_or_insert_with(HashMap::new);
+    match passphrase {
+        Some(passphrase) => {
+            passphrases.insert(path.to_path_buf(), passphrase);
+        }
+        None => {
+            passphrases.remove(path);
+        }
+    }
+}
+
+fn has_passphrase(path: &Path) -> bool {
+    let passphrases = PASSPHRASES.lock().unwrap_or_else(|err| err.into_inner());
+    passphrases
+        .as_ref()
+        .is_some_and(|passphrases| passphrases.contains_key(path))
+}
+
+fn cipher_for(path: &Path, salt: &Salt) -> Result<XChaCha20Poly1305, StorageError> {
+    let mut passphrases = PASSPHRASES.lock().unwrap_or_else(|err| err.into_inner());
+    passphrases
+        .as_mut()
+        .and_then(|passphrases| passphrases.get_mut(path))
+        .ok_or(StorageError::NoPassphrase)?
+        .cipher(salt)
+}
+
+fn new_salt() -> Salt {
+    let mut salt = [0; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+pub fn is_encrypted(contents: &[u8]) -> bool {
+    contents.starts_with(MAGIC)
+}
+
+pub fn is_encrypted_file(path: &Path) -> Result<bool, StorageError> {
+    let mut header = Vec::with_capacity(MAGIC.len());
+    match fs::File::open(path) {
+        Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(false),
+        fd => fd?.take(MAGIC.len() as u64).read_to_end(&mut header)?,
+    };
+    Ok(is_encrypted(&header))
+}
+
+fn salt_of(contents: &[u8]) -> Result<Salt, StorageError> {
+    if contents.len() < HEADER_LEN {
+        return Err(StorageError::TruncatedRecord {
+            field: "encryption header",
+            position: None,
+        });
+    }
+    let mut salt = [0; SALT_LEN];
+    salt.copy_from_slice(&contents[MAGIC.len()..HEADER_LEN]);
+    Ok(salt)
+}
+
+fn encrypt_frame(cipher: &XChaCha20Poly1305, plain: &[u8]) -> Result<Vec<u8>, StorageError> {
+    let mut nonce = [0; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), plain)
+        .map_err(|_| StorageError::Encryption(String::from("Can't encrypt record")))?;
+    let mut frame = Vec::with_capacity(4 + NONCE_LEN + ciphertext.len());
+    frame.extend_from_slice(&(ciphertext.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&nonce);
+    frame.extend_from_slice(&ciphertext);
+    Ok(frame)
+}
+
+// The nonce and ciphertext of the frame starting at `pos`, and where the next
+// one starts
+fn frame_at(
+    contents: &[u8],
+    pos: usize,
+    frame: usize,
+) -> Result<(&XNonce, &[u8], usize), StorageError> {
+    let truncated = StorageError::TruncatedRecord {
+        field: "encrypted record",
+        position: Some(RecordPosition {
+            record: frame,
+            offset: pos as u64,
+        }),
+    };
+    let header_end = pos + 4 + NONCE_LEN;
+    if header_end > contents.len() {
+        return Err(truncated);
+    }
+    let mut len = [0; 4];
+    len.copy_from_slice(&contents[pos..pos + 4]);
+    let frame_end = header_end + u32::from_le_bytes(len) as usize;
+    if frame_end > contents.len() {
+        return Err(truncated);
+    }
+    Ok((
+        XNonce::from_slice(&contents[pos + 4..header_end]),
+        &contents[header_end..frame_end],
+        frame_end,
+    ))
+}
+
+// The plain records of a results file, decrypting them if the file is
+// encrypted. `path` is the results file the contents belong to, which is
+// also what backups of it are decrypted with.
+pub fn plain_contents(path: &Path, contents: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+    if !is_encrypted(&contents) {
+        return Ok(contents);
+    }
+    let cipher = cipher_for(path, &salt_of(&contents)?)?;
+    let mut plain = Vec::with_capacity(contents.len());
+    let mut pos = HEADER_LEN;
+    let mut frames = 0;
+    while pos < contents.len() {
+        let (nonce, ciphertext, frame_end) = frame_at(&contents, pos, frames)?;
+        let record = cipher
+            .decrypt(nonce, ciphertext)
+            .map_err(|_| StorageError::WrongPassphrase)?;
+        plain.extend_from_slice(&record);
+        pos = frame_end;
+        frames += 1;
+    }
+    Ok(plain)
+}
+
+// What could be decrypted of a damaged encrypted file
+#[derive(Debug, Default)]
+pub struct ReadableFrames {
+    pub plain: Vec<u8>,
+    // where each readable frame starts in `plain` and its records in the file
+    frame_starts: Vec<(u64, u64)>,
+    // the byte ranges of the file that couldn't be decrypted, and why
+    pub damaged: Vec<(u64, u64, StorageError)>,
+}
+
+impl ReadableFrames {
+    // Where a position in `plain` is in the file. The cipher is a stream
+    // cipher, so the records keep their length when encrypted.
+    pub fn file_offset(&self, plain_offset: u64) -> u64 {
+        self.frame_starts
+            .iter()
+            .rev()
+            .find(|(plain_start, _)| *plain_start <= plain_offset)
+            .map_or(plain_offset, |(plain_start, file_start)| {
+                file_start + (plain_offset - plain_start)
+            })
+    }
+}
+
+// Decrypts frame by frame, skipping any that don't authenticate and a last
+// one cut short by a crash, so that verify and repair can keep the rest
+pub fn readable_frames(path: &Path, contents: &[u8]) -> Result<ReadableFrames, StorageError> {
+    let cipher = cipher_for(path, &salt_of(contents)?)?;
+    let mut frames = ReadableFrames::default();
+    let mut pos = HEADER_LEN;
+    let mut frame = 0;
+    while pos < contents.len() {
+        let (nonce, ciphertext, frame_end) = match frame_at(contents, pos, frame) {
+            Ok(frame) => frame,
+            Err(error) => {
+                frames
+                    .damaged
+                    .push((pos as u64, contents.len() as u64, error));
+                break;
+            }
+        };
+        match cipher.decrypt(nonce, ciphertext) {
+            Ok(record) => {
+                let file_start = (frame_end - ciphertext.len()) as u64;
+                frames
+                    .frame_starts
+                    .push((frames.plain.len() as u64, file_start));
+                frames.plain.extend_from_slice(&record);
+            }
+            Err(_) => frames.damaged.push((
+                pos as u64,
+                frame_end as u64,
+                StorageError::Encryption(format!("record {} doesn't decrypt", frame)),
+            )),
+        }
+        pos = frame_end;
+        frame += 1;
+    }
+    // with the wrong passphrase nothing decrypts, which isn't damage to repair
+    let nothing_decrypts = frames.frame_starts.is_empty()
+        && frames
+            .damaged
+            .iter()
+            .any(|(_, _, error)| matches!(error, StorageError::Encryption(_)));
+    if nothing_decrypts {
+        return Err(StorageError::WrongPassphrase);
+    }
+    Ok(frames)
+}
+
+// What to append to the results file for these plain records. A new file is
+// encrypted if there's a passphrase for it.
+pub fn contents_to_append(path: &Path, plain: &[u8]) -> Result<Vec<u8>, StorageError> {
+    if file_len(path)? == 0 {
+        return contents_to_write(path, plain, has_passphrase(path));
+    }
+    if !is_encrypted_file(path)? {
+        return Ok(plain.to_vec());
+    }
+    encrypt_frame(&cipher_for(path, &salt_of_file(path)?)?, plain)
+}
+
+fn file_len(path: &Path) -> Result<u64, StorageError> {
+    match fs::metadata(path) {
+        Err(ref error) if error.kind() == ErrorKind::NotFound => Ok(0),
+        metadata => Ok(metadata?.len()),
+    }
+}
+
+// Makes a missing or empty results file an encrypted one with no records yet,
+// as its first append would have
+fn start_encrypted_file(path: &Path) -> Result<(), StorageError> {
+    let _lock = FileLock::exclusive(path)?;
+    if file_len(path)? > 0 {
+        return Ok(());
+    }
+    let salt = new_salt();
+    write_file_atomically(
+        path,
+        &encrypted_contents(&cipher_for(path, &salt)?, &salt, &[])?,
+    )
+}
+
+fn salt_of_file(path: &Path) -> Result<Salt, StorageError> {
+    let mut header = vec![0; HEADER_LEN];
+    fs::File::open(path)?.read_exact(&mut header)?;
+    salt_of(&header)
+}
+
+// Encrypts files kept alongside the results file, like the journal, with the
+// results file's own salt so its already derived key is used every time
+pub struct SidecarEncrypter {
+    salt: Salt,
+    cipher: XChaCha20Poly1305,
+}
+
+impl SidecarEncrypter {
+    // None when the results file isn't encrypted. One that hasn't been
+    // started yet is started encrypted if there's a passphrase for it, so
+    // nothing is kept in plain text before the first result is saved.
+    pub fn for_results(path: &Path) -> Result<Option<Self>, StorageError> {
+        if !is_encrypted_file(path)? {
+            if !has_passphrase(path) {
+                return Ok(None);
+            }
+            start_encrypted_file(path)?;
+            if !is_encrypted_file(path)? {
+                return Ok(None); // it already had results in plain text
+            }
+        }
+        let salt = salt_of_file(path)?;
+        Ok(Some(Self {
+            cipher: cipher_for(path, &salt)?,
+            salt,
+        }))
+    }
+
+    pub fn contents(&self, plain: &[u8]) -> Result<Vec<u8>, StorageError> {
+        encrypted_contents(&self.cipher, &self.salt, plain)
+    }
+}
+
+// The whole of a results file holding these plain records, with a fresh salt
+// when it's encrypted
+pub fn contents_to_write(
+    path: &Path,
+    plain: &[u8],
+    encrypted: bool,
+) -> Result<Vec<u8>, StorageError> {
+    if !encrypted {
+        return Ok(plain.to_vec());
+    }
+    let salt = new_salt();
+    encrypted_contents(&cipher_for(path, &salt)?, &salt, plain)
+}
+
+fn encrypted_contents(
+    cipher: &XChaCha20Poly1305,
+    salt: &Salt,
+    plain: &[u8],
+) -> Result<Vec<u8>, StorageError> {
+    let mut contents = Vec::with_capacity(HEADER_LEN + plain.len() + 64);
+    contents.extend_from_slice(MAGIC);
+    contents.extend_from_slice(salt);
+    if !plain.is_empty() {
+        contents.extend(encrypt_frame(cipher, plain)?);
+    }
+    Ok(contents)
+}
+
+// Re-encrypts the results file and its backups with a new passphrase, or
+// decrypts them for good when there isn't one. Backups that can't be read
+// with the old passphrase are left as they are.
+pub fn change_passphrase(path: &Path, new_passphrase: Option<String>) -> Result<(), StorageError> {
+    let _lock = FileLock::exclusive(path)?;
+    let plain = match fs::read(path) {
+        Err(ref error) if error.kind() == ErrorKind::NotFound => vec![],
+        contents => plain_contents(path, contents?)?,
+    };
+    let backups = backups::list_backups(path)?
+        .into_iter()
+        .filter_map(|backup| {
+            let contents = fs::read(backup.as_path()).ok()?;
+            Some((backup, plain_contents(path, contents).ok()?))
+        })
+        .collect::<Vec<_>>();
+
+    let mut new_passphrase = new_passphrase.map(Passphrase::new);
+    // one salt for all of them, so the key is only derived once
+    let salt = new_salt();
+    let cipher = match &mut new_passphrase {
+        Some(passphrase) => Some(passphrase.cipher(&salt)?),
+        None => None,
+    };
+    let contents_for = |plain: &[u8]| match &cipher {
+        Some(cipher) => encrypted_contents(cipher, &salt, plain),
+        None => Ok(plain.to_vec()),
+    };
+    write_file_atomically(path, &contents_for(&plain)?)?;
+    // the old passphrase is still the right one until the file is rewritten
+    use_passphrase(path, new_passphrase);
+    // the index would give away times and speeds
+    results_index::remove_index(path)?;
+    for (backup, plain) in &backups {
+        write_file_atomically(backup, &contents_for(plain)?)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_encrypted_results_read_back_with_the_passphrase_only() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let typing_result = TypingResult {
+        id: 1,
+        wpm: 50,
+        time: 1556223259,
+        notes: String::from("secret notes"),
+        ..TypingResult::default()
+    };
+    let mut backend = FileBackend::new(path.clone());
+    backend.append(&typing_result).unwrap();
+    change_passphrase(path.as_path(), Some(String::from("correct horse"))).unwrap();
+    backend.append(&typing_result).unwrap();
+    backend.delete(typing_result.id).unwrap();
+    backend.append(&typing_result).unwrap();
+
+    let contents = fs::read(path.as_path()).unwrap();
+    let read_back = read_results_from_path(path.as_path());
+    set_passphrase(path.as_path(), Some(String::from("wrong horse")));
+    let wrong_passphrase = read_results_from_path(path.as_path());
+    set_passphrase(path.as_path(), None);
+    let no_passphrase = read_results_from_path(path.as_path());
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+
+    assert!(is_encrypted(&contents));
+    assert!(!contents
+        .windows(b"secret notes".len())
+        .any(|window| window == b"secret notes"));
+    assert_eq!(vec![typing_result], read_back.unwrap().results);
+    match wrong_passphrase {
+        Err(StorageError::WrongPassphrase) => {}
+        other => panic!("expected a wrong passphrase error, got {:?}", other),
+    }
+    match no_passphrase {
+        Err(StorageError::NoPassphrase) => {}
+        other => panic!("expected a missing passphrase error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_changing_the_passphrase_keeps_backups_readable() {
+    let dir = std::env::temp_dir().join(format!("wpm-test-{}", new_result_id()));
+    let path = dir.join("typing_results.wpm");
+    let typing_result = TypingResult {
+        id: 1,
+        wpm: 50,
+        time: 1556223259,
+        ..TypingResult::default()
+    };
+    FileBackend::new(path.clone())
+        .append(&typing_result)
+        .unwrap();
+    change_passphrase(path.as_path(), Some(String::from("correct horse"))).unwrap();
+    let backup = backups::backup_results(path.as_path(), 7).unwrap();
+    change_passphrase(path.as_path(), Some(String::from("battery staple"))).unwrap();
+    let restored = backups::restore_backup(path.as_path(), backup.as_path());
+    let read_back = read_results_from_path(path.as_path());
+
+    // a failed rewrite keeps the passphrase the file is still encrypted with
+    fs::create_dir_all(dir.join("typing_results.wpm.tmp")).unwrap();
+    let failed_change = change_passphrase(path.as_path(), Some(String::from("wrong horse")));
+    let after_failed_change = read_results_from_path(path.as_path());
+    set_passphrase(path.as_path(), None);
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(restored.is_ok());
+    assert_eq!(vec![typing_result.clone()], read_back.unwrap().results);
+    assert!(failed_change.is_err());
+    assert_eq!(vec![typing_result], after_failed_change.unwrap().results);
+}
@@ -0,0 +1,148 @@
+use crate::storage::*;
+
+// The msgpack log of results, with edits and deletes appended as records
+pub struct FileBackend {
+    path: PathBuf,
+    use_index: bool,
+}
+
+impl FileBackend {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            use_index: true,
+        }
+    }
+
+    // Queries use the sidecar index to decode only the results they return
+    pub fn with_index(self, use_index: bool) -> Self {
+        Self { use_index, ..self }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+}
+
+impl ResultsBackend for FileBackend {
+    fn list(&mut self) -> Result<Vec<TypingResult>, StorageError> {
+        Ok(read_results_from_path(self.path())?.results)
+    }
+
+    fn append(&mut self, typing_result: &TypingResult) -> Result<(), StorageError> {
+        append_record(self.path(), |wr| save_result(wr, typing_result))
+    }
+
+    fn update(&mut self, typing_result: &TypingResult) -> Result<(), StorageError> {
+        append_record(self.path(), |wr| save_replace(wr, typing_result))
+    }
+
+    fn delete(&mut self, id: u64) -> Result<(), StorageError> {
+        append_record(self.path(), |wr| save_delete(wr, id))
+    }
+
+    fn query(&mut self, query: &ResultsQuery) -> Result<Vec<TypingResult>, StorageError> {
+        // the index is only a cache, if it can't be used then read everything
+        if self.use_index {
+            if let Ok(Some(index)) = load_index(self.path()) {
+                match read_indexed_results(self.path(), &index.query(query)) {
+                    Err(StorageError::StaleIndex) => results_index::remove_index(self.path())?,
+                    results => return results,
+                }
+            }
+        }
+        Ok(query.apply(self.list()?))
+    }
+
+    // Upgrades old records and folds away edits once there are enough of them
+    fn compact(&mut self) -> Result<(), StorageError> {
+        if read_results_from_path(self.path())?.needs_compacting() {
+            compact_results_at_path(self.path())?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_append_update_and_delete_results_in_a_file() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let mut backend = FileBackend::new(path.clone());
+    let typing_result1 = TypingResult {
+        id: 1,
+        wpm: 50,
+        time: 1556223259,
+        ..TypingResult::default()
+    };
+    let typing_result2 = TypingResult {
+        id: 2,
+        wpm: 60,
+        time: 1556223359,
+        ..TypingResult::default()
+    };
+
+    backend.append(&typing_result1).unwrap();
+    backend.append(&typing_result2).unwrap();
+    backend
+        .update(&TypingResult {
+            notes: String::from("Edited"),
+            ..typing_result1.clone()
+        })
+        .unwrap();
+    backend.delete(2).unwrap();
+
+    let results = backend.list();
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+    let _ = fs::remove_file(path.with_extension("wpm.idx"));
+
+    assert_eq!(
+        vec![TypingResult {
+            notes: String::from("Edited"),
+            ..typing_result1
+        }],
+        results.unwrap()
+    );
+}
+
+#[test]
+fn test_edits_are_only_folded_away_when_compacting() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let mut backend = FileBackend::new(path.clone());
+    let typing_result = TypingResult {
+        id: 1,
+        wpm: 50,
+        time: 1556223259,
+        ..TypingResult::default()
+    };
+    backend.append(&typing_result).unwrap();
+    for n in 0..COMPACT_AFTER_EDITS {
+        backend
+            .update(&TypingResult {
+                notes: format!("Edit {}", n),
+                ..typing_result.clone()
+            })
+            .unwrap();
+    }
+    let edited_len = fs::metadata(path.as_path()).map(|metadata| metadata.len());
+    let listed = backend.list();
+    let listed_len = fs::metadata(path.as_path()).map(|metadata| metadata.len());
+    let compacted = backend.compact();
+    let compacted_len = fs::metadata(path.as_path()).map(|metadata| metadata.len());
+    let results = backend.list();
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+    let _ = fs::remove_file(path.with_extension("wpm.idx"));
+
+    let edited_len = edited_len.unwrap();
+    assert_eq!(1, listed.unwrap().len());
+    assert_eq!(edited_len, listed_len.unwrap());
+    compacted.unwrap();
+    assert!(compacted_len.unwrap() < edited_len);
+    assert_eq!(
+        vec![TypingResult {
+            notes: format!("Edit {}", COMPACT_AFTER_EDITS - 1),
+            ..typing_result
+        }],
+        results.unwrap()
+    );
+}
@@ -0,0 +1,50 @@
+use fs2::FileExt;
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Advisory lock shared by every wpm process using the same results file.
+// It lives in its own file next to the results so that it survives the
+// results file being replaced when compacting.
+pub struct FileLock {
+    fd: File,
+}
+
+impl FileLock {
+    pub fn shared(path: &Path) -> io::Result<Self> {
+        let fd = Self::open_lock_file(path)?;
+        fd.lock_shared()?;
+        Ok(Self { fd })
+    }
+
+    pub fn exclusive(path: &Path) -> io::Result<Self> {
+        let fd = Self::open_lock_file(path)?;
+        fd.lock_exclusive()?;
+        Ok(Self { fd })
+    }
+
+    fn open_lock_file(path: &Path) -> io::Result<File> {
+        if let Some(dir_name) = path.parent() {
+            DirBuilder::new().recursive(true).create(dir_name)?;
+        }
+        let fd = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_path(path))?;
+        Ok(fd)
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.fd.unlock();
+    }
+}
+
+pub(crate) fn lock_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".lock");
+    path.with_file_name(file_name)
+}
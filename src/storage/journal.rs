@@ -0,0 +1,294 @@
+use crate::storage::encryption::SidecarEncrypter;
+use crate::storage::*;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// The test in progress is written at most this often
+const IN_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// The test being taken, or the last one taken if it hasn't been saved yet.
+// It is kept in a file next to the results so that a crash or quitting by
+// accident doesn't lose it, and is offered back the next time wpm starts.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JournalEntry {
+    InProgress(TypingResult), // the result so far
+    Finished(TypingResult),
+}
+
+#[repr(u8)]
+#[derive(FromPrimitive)]
+enum JournalState {
+    InProgress = 0,
+    Finished = 1,
+}
+
+impl JournalEntry {
+    pub fn typing_result(&self) -> &TypingResult {
+        match self {
+            JournalEntry::InProgress(typing_result) | JournalEntry::Finished(typing_result) => {
+                typing_result
+            }
+        }
+    }
+}
+
+pub fn journal_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".journal");
+    path.with_file_name(file_name)
+}
+
+fn journal_contents(
+    entry: &JournalEntry,
+    encrypter: Option<&SidecarEncrypter>,
+) -> Result<Vec<u8>, StorageError> {
+    let (state, typing_result) = match entry {
+        JournalEntry::InProgress(typing_result) => (JournalState::InProgress, typing_result),
+        JournalEntry::Finished(typing_result) => (JournalState::Finished, typing_result),
+    };
+    let mut buffer = Vec::new();
+    encode::write_u8(&mut buffer, state as u8)?;
+    save_result(&mut buffer, typing_result)?;
+    match encrypter {
+        Some(encrypter) => encrypter.contents(&buffer),
+        None => Ok(buffer),
+    }
+}
+
+fn write_entry(
+    path: &Path,
+    entry: &JournalEntry,
+    encrypter: Option<&SidecarEncrypter>,
+    sync: bool,
+) -> Result<(), StorageError> {
+    if let Some(dir_name) = path.parent() {
+        DirBuilder::new().recursive(true).create(dir_name)?;
+    }
+    replace_file(
+        &journal_path(path),
+        &journal_contents(entry, encrypter)?,
+        sync,
+    )
+}
+
+// `path` is the results file, the journal is encrypted when it is
+pub fn write_journal(path: &Path, entry: &JournalEntry) -> Result<(), StorageError> {
+    let encrypter = SidecarEncrypter::for_results(path)?;
+    write_entry(path, entry, encrypter.as_ref(), true)
+}
+
+// Keeps the journal up to date with the test in progress from a background
+// thread, so typing never waits on the disk. Only the latest result so far is
+// written, at most once per IN_PROGRESS_INTERVAL and without syncing, since
+// losing the last second of a test in a crash is fine. Dropping the writer
+// writes anything still pending and waits for it, so a finished entry or
+// clearing the journal can't be overtaken.
+pub struct JournalWriter {
+    sender: Option<Sender<TypingResult>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl JournalWriter {
+    pub fn new(path: &Path) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let path = path.to_path_buf();
+        let thread = thread::spawn(move || write_in_progress(path.as_path(), &receiver));
+        Self {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn write(&self, typing_result: TypingResult) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(typing_result);
+        }
+    }
+}
+
+impl Drop for JournalWriter {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn write_in_progress(path: &Path, receiver: &Receiver<TypingResult>) {
+    // looked up on the first write, so the key is only derived once per test
+    let mut encrypter = None;
+    let mut write = |typing_result: TypingResult| {
+        if encrypter.is_none() {
+            encrypter = SidecarEncrypter::for_results(path).ok();
+        }
+        // the journal is only a safety net, typing carries on without it
+        if let Some(encrypter) = &encrypter {
+            let entry = JournalEntry::InProgress(typing_result);
+            let _ = write_entry(path, &entry, encrypter.as_ref(), false);
+        }
+    };
+    let mut pending = None;
+    let mut last_write: Option<Instant> = None;
+    loop {
+        let received = match last_write {
+            Some(at) if pending.is_some() => {
+                receiver.recv_timeout(IN_PROGRESS_INTERVAL.saturating_sub(at.elapsed()))
+            }
+            _ => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(typing_result) => pending = Some(typing_result),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if last_write.is_none_or(|at| at.elapsed() >= IN_PROGRESS_INTERVAL) {
+            if let Some(typing_result) = pending.take() {
+                write(typing_result);
+                last_write = Some(Instant::now());
+            }
+        }
+    }
+    if let Some(typing_result) = pending {
+        write(typing_result);
+    }
+}
+
+pub fn read_journal(path: &Path) -> Result<Option<JournalEntry>, StorageError> {
+    let contents = match fs::read(journal_path(path)) {
+        Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        contents => encryption::plain_contents(path, contents?)?,
+    };
+    let rd = &mut &contents[..];
+    let state = decode::read_u8(rd).map_err(StorageError::reading("journal state"))?;
+    let typing_result = match ResultsReader::new(rd).next().transpose()? {
+        Some(Record {
+            kind: RecordKind::Result { typing_result, .. },
+            ..
+        }) => typing_result,
+        _ => return Ok(None),
+    };
+    match FromPrimitive::from_u8(state) {
+        Some(JournalState::InProgress) => Ok(Some(JournalEntry::InProgress(typing_result))),
+        Some(JournalState::Finished) => Ok(Some(JournalEntry::Finished(typing_result))),
+        None => Ok(None),
+    }
+}
+
+pub fn clear_journal(path: &Path) -> Result<(), StorageError> {
+    match fs::remove_file(journal_path(path)) {
+        Err(ref error) if error.kind() == ErrorKind::NotFound => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+#[test]
+fn test_journal_keeps_the_latest_unsaved_result() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let typing_result = TypingResult {
+        id: 3,
+        correct_words: 12,
+        wpm: 48,
+        time: 1556223259,
+        ended_at_ms: 1556223259500,
+        ..TypingResult::default()
+    };
+    let finished_result = TypingResult {
+        correct_words: 50,
+        wpm: 50,
+        ..typing_result.clone()
+    };
+
+    let before = read_journal(path.as_path());
+    write_journal(
+        path.as_path(),
+        &JournalEntry::InProgress(typing_result.clone()),
+    )
+    .unwrap();
+    let in_progress = read_journal(path.as_path());
+    write_journal(
+        path.as_path(),
+        &JournalEntry::Finished(finished_result.clone()),
+    )
+    .unwrap();
+    let finished = read_journal(path.as_path());
+    clear_journal(path.as_path()).unwrap();
+    let after = read_journal(path.as_path());
+
+    assert_eq!(None, before.unwrap());
+    assert_eq!(
+        Some(JournalEntry::InProgress(typing_result)),
+        in_progress.unwrap()
+    );
+    assert_eq!(
+        Some(JournalEntry::Finished(finished_result)),
+        finished.unwrap()
+    );
+    assert_eq!(None, after.unwrap());
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_journal_writer_writes_the_latest_result_so_far() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let typing_result = TypingResult {
+        id: 4,
+        time: 1556223259,
+        ..TypingResult::default()
+    };
+
+    let writer = JournalWriter::new(path.as_path());
+    for correct_words in 1..=20 {
+        writer.write(TypingResult {
+            correct_words,
+            ..typing_result.clone()
+        });
+    }
+    drop(writer);
+    let journal = read_journal(path.as_path());
+    clear_journal(path.as_path()).unwrap();
+
+    assert_eq!(
+        Some(JournalEntry::InProgress(TypingResult {
+            correct_words: 20,
+            ..typing_result
+        })),
+        journal.unwrap()
+    );
+}
+
+#[test]
+fn test_journal_is_encrypted_before_the_first_result_is_saved() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let typing_result = TypingResult {
+        id: 5,
+        correct_words: 7,
+        time: 1556223259,
+        notes: String::from("secret notes"),
+        ..TypingResult::default()
+    };
+    encryption::set_passphrase(path.as_path(), Some(String::from("correct horse")));
+
+    let writer = JournalWriter::new(path.as_path());
+    writer.write(typing_result.clone());
+    drop(writer);
+    let journal_contents = fs::read(journal_path(path.as_path()));
+    let journal = read_journal(path.as_path());
+    let appended = FileBackend::new(path.clone()).append(&typing_result);
+    let results_contents = fs::read(path.as_path());
+    let read_back = read_results_from_path(path.as_path());
+    encryption::set_passphrase(path.as_path(), None);
+    let _ = clear_journal(path.as_path());
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+
+    assert!(encryption::is_encrypted(&journal_contents.unwrap()));
+    assert_eq!(
+        Some(JournalEntry::InProgress(typing_result.clone())),
+        journal.unwrap()
+    );
+    appended.unwrap();
+    assert!(encryption::is_encrypted(&results_contents.unwrap()));
+    assert_eq!(vec![typing_result], read_back.unwrap().results);
+}
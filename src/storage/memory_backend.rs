@@ -0,0 +1,77 @@
+use crate::storage::*;
+
+// Keeps results in memory only, handy for tests
+#[derive(Clone, Debug, Default)]
+pub struct MemoryBackend {
+    results: Vec<TypingResult>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_results(results: Vec<TypingResult>) -> Self {
+        Self { results }
+    }
+}
+
+impl ResultsBackend for MemoryBackend {
+    fn list(&mut self) -> Result<Vec<TypingResult>, StorageError> {
+        Ok(self.results.clone())
+    }
+
+    fn append(&mut self, typing_result: &TypingResult) -> Result<(), StorageError> {
+        self.results.push(typing_result.clone());
+        Ok(())
+    }
+
+    fn update(&mut self, typing_result: &TypingResult) -> Result<(), StorageError> {
+        if let Some(result) = self
+            .results
+            .iter_mut()
+            .find(|result| result.id == typing_result.id)
+        {
+            *result = typing_result.clone();
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, id: u64) -> Result<(), StorageError> {
+        self.results.retain(|result| result.id != id);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_append_update_and_delete_results() {
+    let mut backend = MemoryBackend::new();
+    let typing_result1 = TypingResult {
+        id: 1,
+        wpm: 50,
+        ..TypingResult::default()
+    };
+    let typing_result2 = TypingResult {
+        id: 2,
+        wpm: 60,
+        ..TypingResult::default()
+    };
+
+    backend.append(&typing_result1).unwrap();
+    backend.append(&typing_result2).unwrap();
+    backend
+        .update(&TypingResult {
+            notes: String::from("Edited"),
+            ..typing_result2.clone()
+        })
+        .unwrap();
+    backend.delete(1).unwrap();
+
+    assert_eq!(
+        vec![TypingResult {
+            notes: String::from("Edited"),
+            ..typing_result2
+        }],
+        backend.list().unwrap()
+    );
+}
@@ -0,0 +1,345 @@
+use crate::storage::*;
+use std::fs::File;
+
+const INDEX_VERSION: u8 = 4;
+
+// How many bytes at each end of the indexed part of the results file go into
+// its fingerprint, enough to notice the file being replaced rather than
+// appended to
+const FINGERPRINT_LEN: u64 = 256;
+
+// Where each live result's record starts, with enough of the result to filter
+// and sort on without decoding it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndexEntry {
+    pub offset: u64,
+    pub id: u64,
+    pub time: u64,
+    pub timestamp_ms: u64,
+    pub wpm: i32,
+    pub tags: Vec<String>,
+}
+
+// A sidecar file next to the results file. It is only a cache: appends bring
+// it up to date the next time it is loaded, and rewriting the results file
+// throws it away.
+#[derive(Debug, Default, PartialEq)]
+pub struct ResultsIndex {
+    indexed_len: u64, // how much of the results file the entries cover
+    fingerprint: u64, // of the part the entries cover
+    legacy_records: usize,
+    pub entries: Vec<IndexEntry>,
+}
+
+pub fn index_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".idx");
+    path.with_file_name(file_name)
+}
+
+pub fn remove_index(path: &Path) -> Result<(), StorageError> {
+    match fs::remove_file(index_path(path)) {
+        Err(ref error) if error.kind() == ErrorKind::NotFound => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+impl ResultsIndex {
+    fn read_from<R: Read>(rd: &mut R) -> Result<Self, StorageError> {
+        let read_u64 = |rd: &mut R| decode::read_u64(rd).map_err(StorageError::reading("index"));
+        let version = decode::read_u8(rd).map_err(StorageError::reading("index version"))?;
+        if version != INDEX_VERSION {
+            return Err(StorageError::UnknownVersion {
+                version: version as i8,
+                position: None,
+            });
+        }
+        let mut index = Self {
+            indexed_len: read_u64(rd)?,
+            fingerprint: read_u64(rd)?,
+            legacy_records: read_u64(rd)? as usize,
+            entries: vec![],
+        };
+        loop {
+            let offset = match decode::read_u64(rd) {
+                Ok(offset) => offset,
+                Err(decode::ValueReadError::InvalidMarkerRead(ref error))
+                    if error.kind() == ErrorKind::UnexpectedEof =>
+                {
+                    break
+                }
+                Err(error) => return Err(StorageError::reading("index")(error)),
+            };
+            index.entries.push(IndexEntry {
+                offset,
+                id: read_u64(rd)?,
+                time: read_u64(rd)?,
+                timestamp_ms: read_u64(rd)?,
+                wpm: decode::read_i32(rd).map_err(StorageError::reading("index"))?,
+                tags: read_tags(rd)?,
+            });
+        }
+        Ok(index)
+    }
+
+    fn write_to<W: Write>(&self, wr: &mut W) -> Result<(), StorageError> {
+        encode::write_u8(wr, INDEX_VERSION)?;
+        encode::write_u64(wr, self.indexed_len)?;
+        encode::write_u64(wr, self.fingerprint)?;
+        encode::write_u64(wr, self.legacy_records as u64)?;
+        for entry in &self.entries {
+            encode::write_u64(wr, entry.offset)?;
+            encode::write_u64(wr, entry.id)?;
+            encode::write_u64(wr, entry.time)?;
+            encode::write_u64(wr, entry.timestamp_ms)?;
+            encode::write_i32(wr, entry.wpm)?;
+            write_tags(wr, &entry.tags)?;
+        }
+        Ok(())
+    }
+
+    // Indexes the records from `indexed_len` up to `file_len`. Notes updated
+    // in place can't be pointed at, so false means the index can't be used
+    // until the file is compacted.
+    fn catch_up<R: Read>(&mut self, rd: R, file_len: u64) -> Result<bool, StorageError> {
+        let rd = rd.take(file_len - self.indexed_len);
+        let mut reader = ResultsReader::starting_at(rd, self.indexed_len, self.legacy_records);
+        for record in &mut reader {
+            let Record { offset, kind } = record?;
+            match kind {
+                RecordKind::Result { typing_result, .. } => self.entries.push(IndexEntry {
+                    offset,
+                    id: typing_result.id,
+                    time: typing_result.time,
+                    timestamp_ms: typing_result.timestamp_ms(),
+                    wpm: typing_result.wpm,
+                    tags: typing_result.tags,
+                }),
+                RecordKind::Edit(Edit::Delete(id)) => self.entries.retain(|entry| entry.id != id),
+                RecordKind::Edit(Edit::Replace(typing_result)) => {
+                    if let Some(entry) = self
+                        .entries
+                        .iter_mut()
+                        .find(|entry| entry.id == typing_result.id)
+                    {
+                        entry.offset = offset;
+                        entry.time = typing_result.time;
+                        entry.timestamp_ms = typing_result.timestamp_ms();
+                        entry.wpm = typing_result.wpm;
+                        entry.tags = typing_result.tags;
+                    }
+                }
+                RecordKind::Edit(Edit::UpdateNotes(..)) => return Ok(false),
+                RecordKind::Unknown { .. } => {}
+            }
+        }
+        self.legacy_records = reader.legacy_records();
+        self.indexed_len = file_len;
+        Ok(true)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The same filtering, ordering and paging as `ResultsQuery::apply`
+    pub fn query(&self, query: &ResultsQuery) -> Vec<&IndexEntry> {
+        let mut matching = self
+            .entries
+            .iter()
+            .filter(|entry| query.matches_fields(entry.time, entry.wpm, &entry.tags))
+            .collect::<Vec<_>>();
+        matching.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp_ms));
+        matching
+            .into_iter()
+            .skip(query.skip)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+// A hash of the length and the start and end of the first `len` bytes of the
+// results file
+fn fingerprint(path: &Path, len: u64) -> Result<u64, StorageError> {
+    let mut fd = File::open(path)?;
+    let head_len = len.min(FINGERPRINT_LEN);
+    let mut bytes = vec![0; head_len as usize];
+    fd.read_exact(&mut bytes)?;
+    let tail_start = len.saturating_sub(FINGERPRINT_LEN).max(head_len);
+    let mut tail = vec![0; (len - tail_start) as usize];
+    fd.seek(SeekFrom::Start(tail_start))?;
+    fd.read_exact(&mut tail)?;
+    let hash = fnv_hash(FNV_OFFSET_BASIS, &len.to_le_bytes());
+    Ok(fnv_hash(fnv_hash(hash, &bytes), &tail))
+}
+
+// Loads the index for a results file, bringing it up to date first. None
+// means the file has records the index can't describe, so read it all instead.
+pub fn load_index(path: &Path) -> Result<Option<ResultsIndex>, StorageError> {
+    let _lock = FileLock::shared(path)?;
+    let file_len = match fs::metadata(path) {
+        Err(ref error) if error.kind() == ErrorKind::NotFound => {
+            return Ok(Some(ResultsIndex::default()))
+        }
+        metadata => metadata?.len(),
+    };
+    // record offsets don't survive encryption, and the index would give away
+    // times and speeds anyway
+    if encryption::is_encrypted_file(path)? {
+        return Ok(None);
+    }
+    let mut index = fs::read(index_path(path))
+        .ok()
+        .and_then(|contents| ResultsIndex::read_from(&mut &contents[..]).ok())
+        .filter(|index| index.indexed_len <= file_len)
+        .unwrap_or_default();
+    // a file replaced behind the index's back, e.g. restored or edited by
+    // hand, has to be indexed from the start
+    if index.indexed_len > 0 && index.fingerprint != fingerprint(path, index.indexed_len)? {
+        index = ResultsIndex::default();
+    }
+    if index.indexed_len < file_len {
+        let mut fd = File::open(path)?;
+        fd.seek(SeekFrom::Start(index.indexed_len))?;
+        if !index.catch_up(BufReader::new(fd), file_len)? {
+            return Ok(None);
+        }
+        index.fingerprint = fingerprint(path, file_len)?;
+        let mut buffer = Vec::new();
+        index.write_to(&mut buffer)?;
+        // it's only a cache, so carry on without it if it can't be saved
+        let _ = write_file_atomically(&index_path(path), &buffer);
+    }
+    Ok(Some(index))
+}
+
+// Decodes just the records the entries point at. If one isn't the result the
+// entry says it is, the file changed behind the index's back.
+pub fn read_indexed_results(
+    path: &Path,
+    entries: &[&IndexEntry],
+) -> Result<Vec<TypingResult>, StorageError> {
+    let _lock = FileLock::shared(path)?;
+    let mut fd = BufReader::new(File::open(path)?);
+    let mut results = Vec::with_capacity(entries.len());
+    for entry in entries {
+        fd.seek(SeekFrom::Start(entry.offset))?;
+        let record = ResultsReader::starting_at(&mut fd, entry.offset, 0)
+            .next()
+            .ok_or(StorageError::StaleIndex)??;
+        let (mut typing_result, has_id) = match record.kind {
+            RecordKind::Result {
+                version,
+                typing_result,
+            } => (typing_result, version >= StorageVersions::V4 as i8),
+            RecordKind::Edit(Edit::Replace(typing_result)) => (typing_result, true),
+            _ => return Err(StorageError::StaleIndex),
+        };
+        if (has_id && typing_result.id != entry.id)
+            || typing_result.time != entry.time
+            || typing_result.wpm != entry.wpm
+        {
+            return Err(StorageError::StaleIndex);
+        }
+        // legacy IDs depend on the records before this one, so use the indexed one
+        typing_result.id = entry.id;
+        results.push(typing_result);
+    }
+    Ok(results)
+}
+
+#[test]
+fn test_index_catches_up_with_appends_and_edits() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let mut backend = FileBackend::new(path.clone());
+    let typing_results = (1..=4)
+        .map(|n| TypingResult {
+            id: n,
+            wpm: 40 + n as i32 * 10,
+            time: 1556223259 + n * 60,
+            ..TypingResult::default()
+        })
+        .collect::<Vec<_>>();
+    for typing_result in &typing_results[..3] {
+        backend.append(typing_result).unwrap();
+    }
+    let first_index = load_index(path.as_path());
+
+    let edited_result = TypingResult {
+        notes: String::from("Edited"),
+        tags: vec![String::from("warmup")],
+        wpm: 99,
+        ..typing_results[0].clone()
+    };
+    backend.append(&typing_results[3]).unwrap();
+    backend.update(&edited_result).unwrap();
+    backend.delete(typing_results[1].id).unwrap();
+    let index = load_index(path.as_path()).unwrap().unwrap();
+    let query = ResultsQuery {
+        min_wpm: Some(70),
+        ..ResultsQuery::default()
+    };
+    let results = read_indexed_results(path.as_path(), &index.query(&query));
+    let tag_query = ResultsQuery {
+        tag: Some(String::from("warmup")),
+        ..ResultsQuery::default()
+    };
+    let tagged_results = read_indexed_results(path.as_path(), &index.query(&tag_query));
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+    let _ = fs::remove_file(index_path(path.as_path()));
+
+    assert_eq!(3, first_index.unwrap().unwrap().len());
+    assert_eq!(3, index.len());
+    assert_eq!(
+        vec![
+            typing_results[3].clone(),
+            typing_results[2].clone(),
+            edited_result.clone()
+        ],
+        results.unwrap()
+    );
+    assert_eq!(vec![edited_result], tagged_results.unwrap());
+}
+
+#[test]
+fn test_index_is_rebuilt_when_the_file_is_replaced() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let mut backend = FileBackend::new(path.clone());
+    let typing_results = (1..=3)
+        .map(|n| TypingResult {
+            id: n,
+            wpm: 40 + n as i32 * 10,
+            time: 1556223259 + n * 60,
+            ..TypingResult::default()
+        })
+        .collect::<Vec<_>>();
+    for typing_result in &typing_results {
+        backend.append(typing_result).unwrap();
+    }
+    let stale_index = load_index(path.as_path()).unwrap().unwrap();
+
+    // the same results in another order, e.g. restored from another machine
+    let mut buffer = Vec::new();
+    for typing_result in typing_results.iter().rev() {
+        save_result(&mut buffer, typing_result).unwrap();
+    }
+    fs::write(path.as_path(), &buffer).unwrap();
+    let stale_results =
+        read_indexed_results(path.as_path(), &stale_index.query(&ResultsQuery::default()));
+    let index = load_index(path.as_path()).unwrap().unwrap();
+    let results = read_indexed_results(path.as_path(), &index.query(&ResultsQuery::default()));
+    let queried = backend.query(&ResultsQuery::default());
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+    let _ = fs::remove_file(index_path(path.as_path()));
+
+    assert!(matches!(stale_results, Err(StorageError::StaleIndex)));
+    let newest_first = typing_results.into_iter().rev().collect::<Vec<_>>();
+    assert_eq!(newest_first, results.unwrap());
+    assert_eq!(newest_first, queried.unwrap());
+}
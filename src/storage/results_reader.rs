@@ -0,0 +1,200 @@
+use crate::storage::*;
+
+// Counts the bytes read so each record knows where it started
+struct CountingReader<R: Read> {
+    rd: R,
+    count: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.rd.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+pub enum RecordKind {
+    Result {
+        version: i8,
+        typing_result: TypingResult,
+    },
+    Edit(Edit),
+    Unknown {
+        version: i8,
+    }, // from a newer wpm, we can't tell where it ends
+}
+
+pub struct Record {
+    pub offset: u64, // where the record starts in the file
+    pub kind: RecordKind,
+}
+
+// Decodes one record at a time, so the whole file never has to be in memory.
+// Edits are handed back as they are found rather than applied.
+pub struct ResultsReader<R: Read> {
+    rd: CountingReader<R>,
+    records: usize,
+    legacy_records: usize,
+    finished: bool,
+}
+
+impl<R: Read> ResultsReader<R> {
+    pub fn new(rd: R) -> Self {
+        Self::starting_at(rd, 0, 0)
+    }
+
+    // For reading on from part way through a file, which has already had
+    // `legacy_records` records without IDs
+    pub fn starting_at(rd: R, offset: u64, legacy_records: usize) -> Self {
+        Self {
+            rd: CountingReader { rd, count: offset },
+            records: 0,
+            legacy_records,
+            finished: false,
+        }
+    }
+
+    // Carries on the record numbering used in errors from an earlier reader
+    pub fn with_record_count(self, records: usize) -> Self {
+        Self { records, ..self }
+    }
+
+    pub fn legacy_records(&self) -> usize {
+        self.legacy_records
+    }
+
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    // Where the next record would start
+    pub fn offset(&self) -> u64 {
+        self.rd.count
+    }
+
+    fn read_record(&mut self) -> Result<Option<Record>, StorageError> {
+        loop {
+            let position = RecordPosition {
+                record: self.records,
+                offset: self.rd.count,
+            };
+            match decode::read_marker(&mut self.rd) {
+                Err(decode::MarkerReadError(ref error))
+                    if error.kind() == ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(None)
+                }
+                Err(decode::MarkerReadError(error)) => return Err(error.into()),
+                Ok(Marker::FixExt1) => {
+                    self.records += 1;
+                    let version_num = match decode::read_data_i8(&mut self.rd) {
+                        Ok(version_num) => version_num,
+                        Err(decode::ValueReadError::InvalidMarkerRead(ref error))
+                            if error.kind() == ErrorKind::UnexpectedEof =>
+                        {
+                            return Ok(None)
+                        }
+                        Err(error) => {
+                            return Err(StorageError::reading("version")(error).at(position))
+                        }
+                    };
+                    let kind = self
+                        .read_record_kind(version_num)
+                        .map_err(|error| error.at(position))?;
+                    let offset = position.offset;
+                    return Ok(Some(Record { offset, kind }));
+                }
+                Ok(_) => {}
+            }
+        }
+    }
+
+    fn read_record_kind(&mut self, version_num: i8) -> Result<RecordKind, StorageError> {
+        let rd = &mut self.rd;
+        if let Some(edit_record) = FromPrimitive::from_i8(version_num) {
+            return Ok(RecordKind::Edit(read_edit(rd, edit_record)?));
+        }
+        let mut typing_result = match read_versioned_result(rd, version_num)? {
+            Some(typing_result) => typing_result,
+            None => {
+                return Ok(RecordKind::Unknown {
+                    version: version_num,
+                })
+            }
+        };
+        if typing_result.id == 0 {
+            typing_result.id = legacy_result_id(&typing_result, self.legacy_records);
+            self.legacy_records += 1;
+        }
+        Ok(RecordKind::Result {
+            version: version_num,
+            typing_result,
+        })
+    }
+}
+
+impl<R: Read> Iterator for ResultsReader<R> {
+    type Item = Result<Record, StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let record = self.read_record();
+        if let Ok(None) | Err(_) = record {
+            self.finished = true;
+        }
+        record.transpose()
+    }
+}
+
+#[test]
+fn test_records_are_read_one_at_a_time_with_their_offsets() {
+    let typing_result = TypingResult {
+        id: 7,
+        wpm: 50,
+        time: 1556223259,
+        ..TypingResult::default()
+    };
+    let mut buffer = Vec::new();
+    save_result(&mut buffer, &typing_result).unwrap();
+    let second_offset = buffer.len() as u64;
+    save_delete(&mut buffer, 7).unwrap();
+    // a truncated record
+    let third_offset = buffer.len() as u64;
+    buffer.extend_from_slice(&[0xd4, CURRENT_VERSION as u8]);
+
+    let records = ResultsReader::new(&buffer[..]).collect::<Vec<_>>();
+
+    assert_eq!(3, records.len());
+    match records[0] {
+        Ok(Record {
+            offset: 0,
+            kind: RecordKind::Result {
+                ref typing_result, ..
+            },
+        }) => assert_eq!(7, typing_result.id),
+        _ => panic!("expected the result first"),
+    }
+    match records[1] {
+        Ok(Record {
+            offset,
+            kind: RecordKind::Edit(Edit::Delete(7)),
+        }) => assert_eq!(second_offset, offset),
+        _ => panic!("expected the delete second"),
+    }
+    match records[2] {
+        Err(StorageError::TruncatedRecord {
+            field: "id",
+            position: Some(position),
+        }) => assert_eq!(
+            RecordPosition {
+                record: 2,
+                offset: third_offset
+            },
+            position
+        ),
+        _ => panic!("expected the truncated record to be an error"),
+    }
+}
@@ -0,0 +1,338 @@
+use crate::storage::*;
+use rusqlite::types::{ToSql, Type};
+use rusqlite::{params, params_from_iter, Connection, Row, Transaction};
+
+// Each step takes the schema up one version, add a step when the schema changes
+const MIGRATIONS: [&str; 6] = [
+    "CREATE TABLE typing_results (
+        id INTEGER PRIMARY KEY,
+        correct_words INTEGER NOT NULL,
+        incorrect_words INTEGER NOT NULL,
+        backspaces INTEGER NOT NULL,
+        wpm INTEGER NOT NULL,
+        time INTEGER NOT NULL,
+        notes TEXT NOT NULL
+    );
+    CREATE INDEX typing_results_time ON typing_results (time);",
+    "ALTER TABLE typing_results ADD COLUMN started_at_ms INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE typing_results ADD COLUMN ended_at_ms INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE typing_results ADD COLUMN utc_offset_secs INTEGER NOT NULL DEFAULT 0;",
+    "CREATE TABLE result_tags (
+        result_id INTEGER NOT NULL,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (result_id, tag)
+    );
+    CREATE INDEX result_tags_tag ON result_tags (tag);",
+    "ALTER TABLE typing_results ADD COLUMN timeline TEXT NOT NULL DEFAULT '';",
+    "ALTER TABLE typing_results ADD COLUMN keystrokes TEXT NOT NULL DEFAULT '';",
+    "ALTER TABLE typing_results ADD COLUMN missed_words TEXT NOT NULL DEFAULT '';",
+];
+
+const RESULT_COLUMNS: &str = "id, correct_words, incorrect_words, backspaces, wpm, time, notes, \
+                              started_at_ms, ended_at_ms, utc_offset_secs, timeline, keystrokes, \
+                              missed_words";
+
+// Tags are kept in their own table, and come back with each result joined by newlines
+const TAGS_COLUMN: &str = "(SELECT group_concat(tag, char(10)) FROM result_tags \
+                           WHERE result_id = typing_results.id) AS tags";
+
+pub struct SqliteBackend {
+    connection: Connection,
+}
+
+impl SqliteBackend {
+    pub fn open(path: &Path) -> Result<Self, StorageError> {
+        if let Some(dir_name) = path.parent() {
+            DirBuilder::new().recursive(true).create(dir_name)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, StorageError> {
+        let mut backend = Self { connection };
+        backend.migrate()?;
+        Ok(backend)
+    }
+
+    // Each step commits with its version, so an interrupted migration picks
+    // up where it stopped
+    fn migrate(&mut self) -> Result<(), StorageError> {
+        let schema_version: i32 =
+            self.connection
+                .query_row("PRAGMA user_version", params![], |row| row.get(0))?;
+        for (step, migration) in MIGRATIONS
+            .iter()
+            .enumerate()
+            .skip(schema_version.max(0) as usize)
+        {
+            let transaction = self.connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.execute_batch(&format!("PRAGMA user_version = {}", step + 1))?;
+            transaction.commit()?;
+        }
+        Ok(())
+    }
+}
+
+fn set_tags(transaction: &Transaction, id: u64, tags: &[String]) -> Result<(), StorageError> {
+    transaction.execute(
+        "DELETE FROM result_tags WHERE result_id = ?1",
+        params![id as i64],
+    )?;
+    for tag in tags {
+        transaction.execute(
+            "INSERT INTO result_tags (result_id, tag) VALUES (?1, ?2)",
+            params![id as i64, tag],
+        )?;
+    }
+    Ok(())
+}
+
+// SQLite only has signed integers, so IDs and times are stored as their i64 bit patterns
+fn result_from_row(row: &Row) -> rusqlite::Result<TypingResult> {
+    Ok(TypingResult {
+        id: row.get::<_, i64>(0)? as u64,
+        correct_words: row.get(1)?,
+        incorrect_words: row.get(2)?,
+        backspaces: row.get(3)?,
+        wpm: row.get(4)?,
+        time: row.get::<_, i64>(5)? as u64,
+        notes: row.get(6)?,
+        started_at_ms: row.get::<_, i64>(7)? as u64,
+        ended_at_ms: row.get::<_, i64>(8)? as u64,
+        utc_offset_secs: row.get(9)?,
+        timeline: parse_timeline(&row.get::<_, String>(10)?)
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(10, Type::Text, err.into()))?,
+        keystrokes: parse_keystrokes(&row.get::<_, String>(11)?)
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(11, Type::Text, err.into()))?,
+        missed_words: row
+            .get::<_, String>(12)?
+            .split_whitespace()
+            .map(String::from)
+            .collect(),
+        tags: row
+            .get::<_, Option<String>>(13)?
+            .map(|tags| parse_tags(&tags))
+            .unwrap_or_default(),
+    })
+}
+
+impl ResultsBackend for SqliteBackend {
+    fn list(&mut self) -> Result<Vec<TypingResult>, StorageError> {
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {}, {} FROM typing_results ORDER BY time, ended_at_ms",
+            RESULT_COLUMNS, TAGS_COLUMN
+        ))?;
+        let results = statement
+            .query_map(params![], result_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(results)
+    }
+
+    fn append(&mut self, typing_result: &TypingResult) -> Result<(), StorageError> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            &format!(
+                "INSERT INTO typing_results ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                RESULT_COLUMNS
+            ),
+            params![
+                typing_result.id as i64,
+                typing_result.correct_words,
+                typing_result.incorrect_words,
+                typing_result.backspaces,
+                typing_result.wpm,
+                typing_result.time as i64,
+                typing_result.notes,
+                typing_result.started_at_ms as i64,
+                typing_result.ended_at_ms as i64,
+                typing_result.utc_offset_secs,
+                typing_result.timeline_text(),
+                typing_result.keystrokes_text(),
+                typing_result.missed_words_text(),
+            ],
+        )?;
+        set_tags(&transaction, typing_result.id, &typing_result.tags)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn update(&mut self, typing_result: &TypingResult) -> Result<(), StorageError> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "UPDATE typing_results
+             SET correct_words = ?2, incorrect_words = ?3, backspaces = ?4, wpm = ?5,
+                 time = ?6, notes = ?7, started_at_ms = ?8, ended_at_ms = ?9,
+                 utc_offset_secs = ?10, timeline = ?11, keystrokes = ?12,
+                 missed_words = ?13
+             WHERE id = ?1",
+            params![
+                typing_result.id as i64,
+                typing_result.correct_words,
+                typing_result.incorrect_words,
+                typing_result.backspaces,
+                typing_result.wpm,
+                typing_result.time as i64,
+                typing_result.notes,
+                typing_result.started_at_ms as i64,
+                typing_result.ended_at_ms as i64,
+                typing_result.utc_offset_secs,
+                typing_result.timeline_text(),
+                typing_result.keystrokes_text(),
+                typing_result.missed_words_text(),
+            ],
+        )?;
+        set_tags(&transaction, typing_result.id, &typing_result.tags)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn delete(&mut self, id: u64) -> Result<(), StorageError> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM typing_results WHERE id = ?1",
+            params![id as i64],
+        )?;
+        set_tags(&transaction, id, &[])?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn query(&mut self, query: &ResultsQuery) -> Result<Vec<TypingResult>, StorageError> {
+        let mut conditions = vec![];
+        let mut values: Vec<Box<dyn ToSql>> = vec![];
+        if let Some(since) = query.since {
+            conditions.push("time >= ?");
+            values.push(Box::new(since as i64));
+        }
+        if let Some(until) = query.until {
+            conditions.push("time < ?");
+            values.push(Box::new(until as i64));
+        }
+        if let Some(min_wpm) = query.min_wpm {
+            conditions.push("wpm >= ?");
+            values.push(Box::new(min_wpm));
+        }
+        if let Some(tag) = &query.tag {
+            conditions.push("id IN (SELECT result_id FROM result_tags WHERE tag = ?)");
+            values.push(Box::new(normalise_tag(tag)));
+        }
+
+        let mut sql = format!(
+            "SELECT {}, {} FROM typing_results",
+            RESULT_COLUMNS, TAGS_COLUMN
+        );
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY time DESC, ended_at_ms DESC");
+        // a negative limit means no limit to SQLite, which OFFSET needs
+        let limit = query.limit.map_or(-1, |limit| limit as i64);
+        sql.push_str(&format!(" LIMIT {} OFFSET {}", limit, query.skip));
+
+        let mut statement = self.connection.prepare(&sql)?;
+        let results = statement
+            .query_map(params_from_iter(values.iter()), result_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(results)
+    }
+}
+
+#[test]
+fn test_old_databases_are_migrated_to_the_current_schema() {
+    let connection = Connection::open_in_memory().unwrap();
+    connection
+        .execute_batch(&format!("{}\nPRAGMA user_version = 1;", MIGRATIONS[0]))
+        .unwrap();
+    connection
+        .execute(
+            "INSERT INTO typing_results VALUES (1, 50, 2, 3, 50, 1556223259, 'Old')",
+            params![],
+        )
+        .unwrap();
+
+    let mut backend = SqliteBackend::with_connection(connection).unwrap();
+    let schema_version: i32 = backend
+        .connection
+        .query_row("PRAGMA user_version", params![], |row| row.get(0))
+        .unwrap();
+    assert_eq!(MIGRATIONS.len() as i32, schema_version);
+    assert_eq!(
+        vec![TypingResult {
+            id: 1,
+            correct_words: 50,
+            incorrect_words: 2,
+            backspaces: 3,
+            wpm: 50,
+            time: 1556223259,
+            notes: String::from("Old"),
+            ..TypingResult::default()
+        }],
+        backend.list().unwrap()
+    );
+}
+
+#[test]
+fn test_append_update_delete_and_query_results() {
+    let mut backend = SqliteBackend::open_in_memory().expect("in-memory database to open");
+    let typing_results = (1..=4)
+        .map(|n| TypingResult {
+            id: u64::MAX - n, // doesn't fit in an i64
+            correct_words: 40 + n as i32 * 10,
+            wpm: 40 + n as i32 * 10,
+            time: 1556223259 + n * 60,
+            ..TypingResult::default()
+        })
+        .collect::<Vec<_>>();
+    for typing_result in &typing_results {
+        backend.append(typing_result).unwrap();
+    }
+
+    let edited_result = TypingResult {
+        notes: String::from("Edited"),
+        tags: vec![String::from("ergodox"), String::from("tired")],
+        timeline: vec![WpmSample {
+            raw_wpm: 62.4,
+            net_wpm: 50.0,
+        }],
+        keystrokes: vec![Keystroke {
+            expected: Some('a'),
+            typed: 's',
+            interval_ms: 140,
+        }],
+        missed_words: vec![String::from("ergodox")],
+        ..typing_results[1].clone()
+    };
+    backend.update(&edited_result).unwrap();
+    backend.delete(typing_results[0].id).unwrap();
+
+    assert_eq!(
+        vec![
+            edited_result.clone(),
+            typing_results[2].clone(),
+            typing_results[3].clone()
+        ],
+        backend.list().unwrap()
+    );
+
+    let query = ResultsQuery {
+        min_wpm: Some(60),
+        until: Some(typing_results[3].time),
+        ..ResultsQuery::default()
+    };
+    assert_eq!(
+        vec![typing_results[2].clone(), edited_result.clone()],
+        backend.query(&query).unwrap()
+    );
+
+    let tag_query = ResultsQuery {
+        tag: Some(String::from("Tired")),
+        ..ResultsQuery::default()
+    };
+    assert_eq!(vec![edited_result], backend.query(&tag_query).unwrap());
+}
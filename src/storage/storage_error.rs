@@ -1,30 +1,166 @@
 use rmp::*;
 use std::error;
 use std::fmt;
+use std::io;
+use std::str::Utf8Error;
 
+// Which record an error was found in, counting every record in the file from
+// zero, and the byte offset the record starts at
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RecordPosition {
+    pub record: usize,
+    pub offset: u64,
+}
+
+impl fmt::Display for RecordPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "record {} (byte {})", self.record, self.offset)
+    }
+}
+
+// Errors found while decoding a record have no position until the reader
+// fills it in with `at`
 #[derive(Debug)]
 pub enum StorageError {
-    MissingCorrectWords(decode::ValueReadError),
-    MissingIncorrectWords(decode::ValueReadError),
-    MissingBackspaces(decode::ValueReadError),
-    MissingWpm(decode::ValueReadError),
-    MissingTime(decode::ValueReadError),
-    MissingNotesLen(decode::ValueReadError),
+    Io(io::Error),
+    UnknownVersion {
+        version: i8,
+        position: Option<RecordPosition>,
+    },
+    TruncatedRecord {
+        field: &'static str,
+        position: Option<RecordPosition>,
+    },
+    UnexpectedValue {
+        field: &'static str,
+        marker: Marker,
+        position: Option<RecordPosition>,
+    },
+    InvalidText {
+        field: &'static str,
+        source: Utf8Error,
+        position: Option<RecordPosition>,
+    },
+    TooLarge {
+        field: &'static str,
+        len: u64,
+        limit: u64,
+        position: Option<RecordPosition>,
+    },
+    StaleIndex,
+    NoPassphrase,
+    WrongPassphrase,
+    Encryption(String),
+    NoDataDir,
+    NoSqliteSupport,
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+}
+
+impl StorageError {
+    // For `map_err` when decoding a field of a record
+    pub fn reading(field: &'static str) -> impl Fn(decode::ValueReadError) -> Self {
+        move |error| match error {
+            decode::ValueReadError::InvalidMarkerRead(error)
+            | decode::ValueReadError::InvalidDataRead(error) => Self::from_field_io(field, error),
+            decode::ValueReadError::TypeMismatch(marker) => StorageError::UnexpectedValue {
+                field,
+                marker,
+                position: None,
+            },
+        }
+    }
+
+    pub fn from_field_io(field: &'static str, error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::UnexpectedEof {
+            StorageError::TruncatedRecord {
+                field,
+                position: None,
+            }
+        } else {
+            StorageError::Io(error)
+        }
+    }
+
+    pub fn at(mut self, record_position: RecordPosition) -> Self {
+        match self {
+            StorageError::UnknownVersion {
+                ref mut position, ..
+            }
+            | StorageError::TruncatedRecord {
+                ref mut position, ..
+            }
+            | StorageError::UnexpectedValue {
+                ref mut position, ..
+            }
+            | StorageError::InvalidText {
+                ref mut position, ..
+            }
+            | StorageError::TooLarge {
+                ref mut position, ..
+            } => *position = Some(record_position),
+            _ => {}
+        }
+        self
+    }
+
+    pub fn position(&self) -> Option<RecordPosition> {
+        match *self {
+            StorageError::UnknownVersion { position, .. }
+            | StorageError::TruncatedRecord { position, .. }
+            | StorageError::UnexpectedValue { position, .. }
+            | StorageError::InvalidText { position, .. }
+            | StorageError::TooLarge { position, .. } => position,
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for StorageError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(position) = self.position() {
+            write!(f, "{}: ", position)?;
+        }
         match *self {
-            StorageError::MissingCorrectWords(ref err) => {
-                write!(f, "ValueReadError error: {}", err)
+            StorageError::Io(ref err) => write!(f, "{}", err),
+            StorageError::UnknownVersion { version, .. } => {
+                write!(f, "unknown record version {}", version)
+            }
+            StorageError::TruncatedRecord { field, .. } => {
+                write!(f, "record is cut short, {} is missing", field)
+            }
+            StorageError::UnexpectedValue { field, marker, .. } => {
+                write!(f, "expected {} but found {:?}", field, marker)
             }
-            StorageError::MissingIncorrectWords(ref err) => {
-                write!(f, "ValueReadError error: {}", err)
+            StorageError::InvalidText {
+                field, ref source, ..
+            } => write!(f, "{} isn't valid UTF-8: {}", field, source),
+            StorageError::TooLarge {
+                field, len, limit, ..
+            } => write!(
+                f,
+                "{} is {} bytes long, more than the limit of {}",
+                field, len, limit
+            ),
+            StorageError::StaleIndex => {
+                write!(f, "The results index doesn't match the results file")
             }
-            StorageError::MissingBackspaces(ref err) => write!(f, "ValueReadError error: {}", err),
-            StorageError::MissingWpm(ref err) => write!(f, "ValueReadError error: {}", err),
-            StorageError::MissingTime(ref err) => write!(f, "ValueReadError error: {}", err),
-            StorageError::MissingNotesLen(ref err) => write!(f, "ValueReadError error: {}", err),
+            StorageError::NoPassphrase => write!(
+                f,
+                "The results file is encrypted, set WPM_PASSPHRASE or enter the passphrase"
+            ),
+            StorageError::WrongPassphrase => write!(
+                f,
+                "Can't decrypt the results file, the passphrase is wrong or the file is damaged"
+            ),
+            StorageError::Encryption(ref message) => write!(f, "Encryption error: {}", message),
+            StorageError::NoDataDir => write!(
+                f,
+                "No data directory to keep results in, set WPM_RESULTS_FILE or --results-file"
+            ),
+            StorageError::NoSqliteSupport => write!(f, "wpm was built without SQLite support"),
+            #[cfg(feature = "sqlite")]
+            StorageError::Sqlite(ref err) => write!(f, "SQLite error: {}", err),
         }
     }
 }
@@ -32,12 +168,46 @@ impl fmt::Display for StorageError {
 impl error::Error for StorageError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
-            StorageError::MissingCorrectWords(ref err) => Some(err),
-            StorageError::MissingIncorrectWords(ref err) => Some(err),
-            StorageError::MissingBackspaces(ref err) => Some(err),
-            StorageError::MissingWpm(ref err) => Some(err),
-            StorageError::MissingTime(ref err) => Some(err),
-            StorageError::MissingNotesLen(ref err) => Some(err),
+            StorageError::Io(ref err) => Some(err),
+            StorageError::InvalidText { ref source, .. } => Some(source),
+            #[cfg(feature = "sqlite")]
+            StorageError::Sqlite(ref err) => Some(err),
+            _ => None,
         }
     }
 }
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+impl From<encode::ValueWriteError> for StorageError {
+    fn from(err: encode::ValueWriteError) -> Self {
+        StorageError::Io(err.into())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        StorageError::Sqlite(err)
+    }
+}
+
+#[test]
+fn test_errors_say_which_record_they_are_in() {
+    let error = StorageError::reading("wpm")(decode::ValueReadError::InvalidDataRead(
+        io::Error::from(io::ErrorKind::UnexpectedEof),
+    ))
+    .at(RecordPosition {
+        record: 12,
+        offset: 345,
+    });
+
+    assert_eq!(
+        "record 12 (byte 345): record is cut short, wpm is missing",
+        error.to_string()
+    );
+}
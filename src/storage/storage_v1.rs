@@ -1,16 +1,12 @@
 use crate::storage::*;
 use crate::TypingResult;
 use rmp::{decode, encode};
-use std::error::Error;
 use std::io::{Read, Write};
 
 pub struct StorageV1 {}
 
 impl Storage for StorageV1 {
-    fn save_result<W: Write>(
-        wr: &mut W,
-        typing_result: &TypingResult,
-    ) -> Result<(), Box<dyn Error>> {
+    fn save_result<W: Write>(wr: &mut W, typing_result: &TypingResult) -> Result<(), StorageError> {
         encode::write_i32(wr, typing_result.correct_words)?;
         encode::write_i32(wr, typing_result.incorrect_words)?;
         encode::write_i32(wr, typing_result.backspaces)?;
@@ -18,15 +14,16 @@ impl Storage for StorageV1 {
         Ok(())
     }
 
-    fn read_result<R: Read>(rd: &mut R) -> Result<TypingResult, Box<dyn Error>> {
+    fn read_result<R: Read>(rd: &mut R) -> Result<TypingResult, StorageError> {
         let mut typing_result = TypingResult::default();
 
         typing_result.correct_words =
-            decode::read_i32(rd).map_err(StorageError::MissingCorrectWords)?;
+            decode::read_i32(rd).map_err(StorageError::reading("correct words"))?;
         typing_result.incorrect_words =
-            decode::read_i32(rd).map_err(StorageError::MissingIncorrectWords)?;
-        typing_result.backspaces = decode::read_i32(rd).map_err(StorageError::MissingBackspaces)?;
-        typing_result.wpm = decode::read_i32(rd).map_err(StorageError::MissingWpm)?;
+            decode::read_i32(rd).map_err(StorageError::reading("incorrect words"))?;
+        typing_result.backspaces =
+            decode::read_i32(rd).map_err(StorageError::reading("backspaces"))?;
+        typing_result.wpm = decode::read_i32(rd).map_err(StorageError::reading("wpm"))?;
         Ok(typing_result)
     }
 }
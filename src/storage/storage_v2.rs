@@ -1,16 +1,12 @@
 use crate::storage::*;
 use crate::TypingResult;
 use rmp::{decode, encode};
-use std::error::Error;
 use std::io::{Read, Write};
 
 pub struct StorageV2 {}
 
 impl Storage for StorageV2 {
-    fn save_result<W: Write>(
-        wr: &mut W,
-        typing_result: &TypingResult,
-    ) -> Result<(), Box<dyn Error>> {
+    fn save_result<W: Write>(wr: &mut W, typing_result: &TypingResult) -> Result<(), StorageError> {
         encode::write_i32(wr, typing_result.correct_words)?;
         encode::write_i32(wr, typing_result.incorrect_words)?;
         encode::write_i32(wr, typing_result.backspaces)?;
@@ -19,16 +15,17 @@ impl Storage for StorageV2 {
         Ok(())
     }
 
-    fn read_result<R: Read>(rd: &mut R) -> Result<TypingResult, Box<dyn Error>> {
+    fn read_result<R: Read>(rd: &mut R) -> Result<TypingResult, StorageError> {
         let mut typing_result = TypingResult::default();
 
         typing_result.correct_words =
-            decode::read_i32(rd).map_err(StorageError::MissingCorrectWords)?;
+            decode::read_i32(rd).map_err(StorageError::reading("correct words"))?;
         typing_result.incorrect_words =
-            decode::read_i32(rd).map_err(StorageError::MissingIncorrectWords)?;
-        typing_result.backspaces = decode::read_i32(rd).map_err(StorageError::MissingBackspaces)?;
-        typing_result.wpm = decode::read_i32(rd).map_err(StorageError::MissingWpm)?;
-        typing_result.time = decode::read_u64(rd).map_err(StorageError::MissingTime)?;
+            decode::read_i32(rd).map_err(StorageError::reading("incorrect words"))?;
+        typing_result.backspaces =
+            decode::read_i32(rd).map_err(StorageError::reading("backspaces"))?;
+        typing_result.wpm = decode::read_i32(rd).map_err(StorageError::reading("wpm"))?;
+        typing_result.time = decode::read_u64(rd).map_err(StorageError::reading("time"))?;
 
         Ok(typing_result)
     }
@@ -1,14 +1,10 @@
 use crate::storage::*;
-use std::error::Error;
 use std::io::{Read, Write};
 
 pub struct StorageV3 {}
 
 impl Storage for StorageV3 {
-    fn save_result<W: Write>(
-        wr: &mut W,
-        typing_result: &TypingResult,
-    ) -> Result<(), Box<dyn Error>> {
+    fn save_result<W: Write>(wr: &mut W, typing_result: &TypingResult) -> Result<(), StorageError> {
         encode::write_i32(wr, typing_result.correct_words)?;
         encode::write_i32(wr, typing_result.incorrect_words)?;
         encode::write_i32(wr, typing_result.backspaces)?;
@@ -19,25 +15,18 @@ impl Storage for StorageV3 {
         Ok(())
     }
 
-    fn read_result<R: Read>(rd: &mut R) -> Result<TypingResult, Box<dyn Error>> {
+    fn read_result<R: Read>(rd: &mut R) -> Result<TypingResult, StorageError> {
         let mut typing_result = TypingResult::default();
 
         typing_result.correct_words =
-            decode::read_i32(rd).map_err(StorageError::MissingCorrectWords)?;
+            decode::read_i32(rd).map_err(StorageError::reading("correct words"))?;
         typing_result.incorrect_words =
-            decode::read_i32(rd).map_err(StorageError::MissingIncorrectWords)?;
-        typing_result.backspaces = decode::read_i32(rd).map_err(StorageError::MissingBackspaces)?;
-        typing_result.wpm = decode::read_i32(rd).map_err(StorageError::MissingWpm)?;
-        typing_result.time = decode::read_u64(rd).map_err(StorageError::MissingTime)?;
-        let notes_len = decode::read_str_len(rd).map_err(StorageError::MissingNotesLen)?;
-        let mut notes = vec![0; notes_len as usize];
-
-        match decode::read_str(rd, &mut notes.as_mut_slice()) {
-            Ok(notes_value) => {
-                typing_result.notes = String::from(notes_value);
-            }
-            Err(err) => Err(format!("{:?}", err))?,
-        }
+            decode::read_i32(rd).map_err(StorageError::reading("incorrect words"))?;
+        typing_result.backspaces =
+            decode::read_i32(rd).map_err(StorageError::reading("backspaces"))?;
+        typing_result.wpm = decode::read_i32(rd).map_err(StorageError::reading("wpm"))?;
+        typing_result.time = decode::read_u64(rd).map_err(StorageError::reading("time"))?;
+        typing_result.notes = read_notes(rd)?;
 
         Ok(typing_result)
     }
@@ -0,0 +1,69 @@
+use crate::storage::*;
+use std::io::{Read, Write};
+
+pub struct StorageV5 {}
+
+impl Storage for StorageV5 {
+    fn save_result<W: Write>(wr: &mut W, typing_result: &TypingResult) -> Result<(), StorageError> {
+        encode::write_u64(wr, typing_result.id)?;
+        encode::write_i32(wr, typing_result.correct_words)?;
+        encode::write_i32(wr, typing_result.incorrect_words)?;
+        encode::write_i32(wr, typing_result.backspaces)?;
+        encode::write_i32(wr, typing_result.wpm)?;
+        encode::write_u64(wr, typing_result.time)?;
+        encode::write_str_len(wr, typing_result.notes.len() as u32)?;
+        encode::write_str(wr, &typing_result.notes)?;
+        encode::write_u64(wr, typing_result.started_at_ms)?;
+        encode::write_u64(wr, typing_result.ended_at_ms)?;
+        encode::write_i32(wr, typing_result.utc_offset_secs)?;
+        Ok(())
+    }
+
+    fn read_result<R: Read>(rd: &mut R) -> Result<TypingResult, StorageError> {
+        let mut typing_result = TypingResult::default();
+
+        typing_result.id = decode::read_u64(rd).map_err(StorageError::reading("id"))?;
+        typing_result.correct_words =
+            decode::read_i32(rd).map_err(StorageError::reading("correct words"))?;
+        typing_result.incorrect_words =
+            decode::read_i32(rd).map_err(StorageError::reading("incorrect words"))?;
+        typing_result.backspaces =
+            decode::read_i32(rd).map_err(StorageError::reading("backspaces"))?;
+        typing_result.wpm = decode::read_i32(rd).map_err(StorageError::reading("wpm"))?;
+        typing_result.time = decode::read_u64(rd).map_err(StorageError::reading("time"))?;
+        typing_result.notes = read_notes(rd)?;
+        typing_result.started_at_ms =
+            decode::read_u64(rd).map_err(StorageError::reading("start time"))?;
+        typing_result.ended_at_ms =
+            decode::read_u64(rd).map_err(StorageError::reading("end time"))?;
+        typing_result.utc_offset_secs =
+            decode::read_i32(rd).map_err(StorageError::reading("UTC offset"))?;
+
+        Ok(typing_result)
+    }
+}
+
+#[test]
+fn test_write_new_typing_result_to_blank_file_and_read_it_back() {
+    let mut buffer = Vec::new();
+
+    let typing_result = TypingResult {
+        id: 0x5eed_cafe_f00d_d00d,
+        correct_words: 87,
+        incorrect_words: 3,
+        backspaces: 2,
+        wpm: 87,
+        time: 1556223259,
+        notes: String::from("This is a typing result."),
+        started_at_ms: 1556223199042,
+        ended_at_ms: 1556223259042,
+        utc_offset_secs: -5 * 3600,
+        ..TypingResult::default()
+    };
+
+    let _ = StorageV5::save_result(&mut buffer, &typing_result);
+
+    let result = StorageV5::read_result(&mut &buffer[..]).expect("Read back the results");
+
+    assert_eq!(typing_result, result);
+}
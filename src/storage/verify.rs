@@ -0,0 +1,242 @@
+use crate::storage::*;
+use std::collections::BTreeMap;
+
+// Bytes that couldn't be read as records, and why
+#[derive(Debug, PartialEq)]
+pub struct DamagedRegion {
+    pub start: u64,
+    pub end: u64,
+    pub error: String,
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub records_by_version: BTreeMap<i8, usize>,
+    pub edit_records: usize,
+    pub records_needing_upgrade: usize,
+    pub damaged_regions: Vec<DamagedRegion>,
+    pub readable: ReadTypingResults, // everything we could read, with edits applied
+}
+
+impl VerifyReport {
+    pub fn is_damaged(&self) -> bool {
+        !self.damaged_regions.is_empty()
+    }
+
+    fn damaged(&mut self, start: u64, end: u64, error: String) {
+        self.damaged_regions
+            .push(DamagedRegion { start, end, error });
+    }
+}
+
+// Reads every record it can. After a record that can't be read, it carries on
+// from the next one that can, so a damaged region doesn't lose the rest.
+fn verify_buffer(buffer: &[u8]) -> VerifyReport {
+    let mut report = VerifyReport::default();
+    let mut pos = 0;
+    let mut records = 0;
+    let mut legacy_records = 0;
+    let mut damaged_from: Option<(u64, String)> = None;
+    let end = buffer.len() as u64;
+
+    while pos < end {
+        let mut reader = ResultsReader::starting_at(&buffer[pos as usize..], pos, legacy_records)
+            .with_record_count(records);
+        // where the next record should start if there are no stray bytes
+        let mut expected = pos;
+        let mut after_unknown = false;
+        let mut failed = None;
+        while let Some(record) = reader.next() {
+            let Record { offset, kind } = match record {
+                Ok(record) => record,
+                Err(error) => {
+                    failed = Some(error);
+                    break;
+                }
+            };
+            if let Some((start, error)) = damaged_from.take() {
+                report.damaged(start, offset, error);
+            } else if offset > expected && !after_unknown {
+                report.damaged(
+                    expected,
+                    offset,
+                    String::from("unexpected bytes between records"),
+                );
+            }
+            after_unknown = false;
+            match kind {
+                RecordKind::Result {
+                    version,
+                    typing_result,
+                } => {
+                    *report.records_by_version.entry(version).or_insert(0) += 1;
+                    if version < CURRENT_VERSION {
+                        report.records_needing_upgrade += 1;
+                        report.readable.records_need_upgrading = true;
+                    }
+                    report.readable.results.push(typing_result);
+                }
+                RecordKind::Edit(edit) => {
+                    report.edit_records += 1;
+                    report.readable.apply_edit(edit);
+                }
+                RecordKind::Unknown { version } => {
+                    *report.records_by_version.entry(version).or_insert(0) += 1;
+                    after_unknown = true;
+                }
+            }
+            expected = reader.offset();
+        }
+        records = reader.records();
+        legacy_records = reader.legacy_records();
+
+        match failed {
+            Some(error) => {
+                let start = error
+                    .position()
+                    .map_or(expected, |position| position.offset);
+                if damaged_from.is_none() {
+                    damaged_from = Some((start, error.to_string()));
+                }
+                pos = start + 1;
+            }
+            None => {
+                if let Some((start, error)) = damaged_from.take() {
+                    report.damaged(start, end, error);
+                } else if expected < end && !after_unknown {
+                    report.damaged(expected, end, String::from("unexpected bytes at the end"));
+                }
+                break;
+            }
+        }
+    }
+    if let Some((start, error)) = damaged_from {
+        report.damaged(start, end, error);
+    }
+    report
+}
+
+// An encrypted file is decrypted frame by frame, so a frame that can't be
+// is reported as damaged rather than losing the rest
+fn verify_contents(path: &Path, contents: &[u8]) -> Result<VerifyReport, StorageError> {
+    if !encryption::is_encrypted(contents) {
+        return Ok(verify_buffer(contents));
+    }
+    let frames = encryption::readable_frames(path, contents)?;
+    let mut report = verify_buffer(&frames.plain);
+    for region in &mut report.damaged_regions {
+        region.start = frames.file_offset(region.start);
+        region.end = frames.file_offset(region.end);
+    }
+    for (start, end, error) in frames.damaged {
+        report.damaged(start, end, error.to_string());
+    }
+    report.damaged_regions.sort_by_key(|region| region.start);
+    Ok(report)
+}
+
+pub fn verify_results_file(path: &Path) -> Result<VerifyReport, StorageError> {
+    let _lock = FileLock::shared(path)?;
+    verify_contents(path, &fs::read(path)?)
+}
+
+fn damaged_copy_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".damaged");
+    path.with_file_name(file_name)
+}
+
+// Rewrites the readable results to a fresh file at the current version. The
+// damaged file is kept next to it, and its path returned, in case there is
+// more to be rescued by hand.
+pub fn repair_results_file(path: &Path) -> Result<(VerifyReport, Option<PathBuf>), StorageError> {
+    let _lock = FileLock::exclusive(path)?;
+    let contents = fs::read(path)?;
+    let report = verify_contents(path, &contents)?;
+    let damaged_copy = if report.is_damaged() {
+        let damaged_copy = damaged_copy_path(path);
+        write_file_atomically(damaged_copy.as_path(), &contents)?;
+        Some(damaged_copy)
+    } else {
+        None
+    };
+    write_results_to_path_unlocked(path, &report.readable.results)?;
+    Ok((report, damaged_copy))
+}
+
+#[test]
+fn test_verify_carries_on_past_damaged_records() {
+    let typing_results = (1..=3)
+        .map(|n| TypingResult {
+            id: n,
+            wpm: 40 + n as i32 * 10,
+            time: 1556223259 + n * 60,
+            ..TypingResult::default()
+        })
+        .collect::<Vec<_>>();
+    let mut buffer = Vec::new();
+    encode::write_ext_meta(&mut buffer, 1, StorageVersions::V2 as i8).unwrap();
+    storage_v2::StorageV2::save_result(&mut buffer, &typing_results[0]).unwrap();
+    let damaged_start = buffer.len() as u64;
+    // the second result is cut off part way through its fields
+    let mut second = Vec::new();
+    save_result(&mut second, &typing_results[1]).unwrap();
+    buffer.extend_from_slice(&second[..12]);
+    let damaged_end = buffer.len() as u64;
+    save_result(&mut buffer, &typing_results[2]).unwrap();
+
+    let report = verify_buffer(&buffer);
+
+    assert_eq!(
+        Some(&1),
+        report.records_by_version.get(&(StorageVersions::V2 as i8))
+    );
+    assert_eq!(Some(&1), report.records_by_version.get(&CURRENT_VERSION));
+    assert_eq!(1, report.records_needing_upgrade);
+    assert_eq!(1, report.damaged_regions.len());
+    assert_eq!(damaged_start, report.damaged_regions[0].start);
+    assert_eq!(damaged_end, report.damaged_regions[0].end);
+    assert!(report.damaged_regions[0].error.starts_with("record 1 "));
+    assert_eq!(2, report.readable.results.len());
+    assert_eq!(typing_results[2], report.readable.results[1]);
+}
+
+#[test]
+fn test_repair_keeps_the_frames_of_an_encrypted_file_that_decrypt() {
+    let path = std::env::temp_dir().join(format!("wpm-test-{}.wpm", new_result_id()));
+    let typing_results = (1..=2)
+        .map(|n| TypingResult {
+            id: n,
+            wpm: 40 + n as i32 * 10,
+            time: 1556223259 + n * 60,
+            ..TypingResult::default()
+        })
+        .collect::<Vec<_>>();
+    let mut backend = FileBackend::new(path.clone());
+    encryption::change_passphrase(path.as_path(), Some(String::from("correct horse"))).unwrap();
+    backend.append(&typing_results[0]).unwrap();
+    let first_frame_end = fs::metadata(path.as_path()).unwrap().len();
+    backend.append(&typing_results[1]).unwrap();
+    // a crash part way through appending the second result
+    let contents = fs::read(path.as_path()).unwrap();
+    fs::write(path.as_path(), &contents[..contents.len() - 5]).unwrap();
+
+    let report = verify_results_file(path.as_path());
+    let repaired = repair_results_file(path.as_path());
+    let read_back = read_results_from_path(path.as_path());
+    let still_encrypted = encryption::is_encrypted_file(path.as_path());
+    encryption::set_passphrase(path.as_path(), None);
+    let _ = fs::remove_file(path.as_path());
+    let _ = fs::remove_file(damaged_copy_path(path.as_path()));
+    let _ = fs::remove_file(path.with_extension("wpm.lock"));
+    let _ = fs::remove_file(path.with_extension("wpm.idx"));
+
+    let report = report.unwrap();
+    assert_eq!(vec![typing_results[0].clone()], report.readable.results);
+    assert_eq!(1, report.damaged_regions.len());
+    assert_eq!(first_frame_end, report.damaged_regions[0].start);
+    assert_eq!(contents.len() as u64 - 5, report.damaged_regions[0].end);
+    assert!(repaired.unwrap().1.is_some());
+    assert_eq!(vec![typing_results[0].clone()], read_back.unwrap().results);
+    assert!(still_encrypted.unwrap());
+}
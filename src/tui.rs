@@ -0,0 +1,278 @@
+use crate::config::Config;
+use crate::*;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+use std::error::Error;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+
+const LINES_SHOWN: u16 = 3;
+const TIMER_ROW: u16 = 0;
+const FIRST_WORDS_ROW: u16 = 2;
+const INPUT_ROW: u16 = FIRST_WORDS_ROW + LINES_SHOWN + 1;
+const TICK: Duration = Duration::from_millis(100); // how often the countdown is redrawn
+
+// Raw mode on the alternate screen for the length of the test. The terminal
+// is put back when this is dropped, panics included.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let raw_mode = RawMode;
+        execute!(
+            io::stdout(),
+            terminal::EnterAlternateScreen,
+            terminal::Clear(ClearType::All)
+        )?;
+        Ok(raw_mode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// Takes a typing test in the terminal, for when there's no window to open.
+// A practice test is weighted towards what the stored results show is weak.
+pub fn run(config: &Config, practice: bool) -> Result<(), Box<dyn Error>> {
+    let journal_path = storage::results_path(config).ok();
+    if let Some(path) = &journal_path {
+        recover_journal(config, path)?;
+    }
+    let mut typing_test = TypingTest::default();
+    if practice {
+        let results = storage::open_backend(config)?.list()?;
+        typing_test.practice(&results);
+    } else {
+        typing_test.top200();
+    }
+    typing_test.duration = Some(config.default_test_duration);
+
+    let finished = {
+        let _raw_mode = RawMode::enable()?;
+        // dropped once the test is over, so nothing is left to be written
+        let journal = journal_path.as_deref().map(storage::JournalWriter::new);
+        take_test(&mut typing_test, journal.as_ref())?
+    };
+    if !finished {
+        if let Some(path) = &journal_path {
+            storage::clear_journal(path)?;
+        }
+        println!("Test abandoned");
+        return Ok(());
+    }
+
+    let typing_result = typing_test.result();
+    print_summary(&typing_result);
+    save_result(config, typing_result, journal_path.as_deref())
+}
+
+// False if the test was abandoned
+fn take_test(
+    typing_test: &mut TypingTest,
+    journal: Option<&storage::JournalWriter>,
+) -> Result<bool, Box<dyn Error>> {
+    let mut stdout = io::stdout();
+    loop {
+        if typing_test.is_done() == Some(true) {
+            typing_test.end();
+            return Ok(true);
+        }
+        draw(&mut stdout, typing_test)?;
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let (code, modifiers) = match event::read()? {
+            // some terminals report key releases too
+            Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind,
+                ..
+            }) if kind != KeyEventKind::Release => (code, modifiers),
+            _ => continue,
+        };
+        match code {
+            KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(false),
+            // typing the char changes the test, so it stays out of the guard
+            #[allow(clippy::collapsible_match)]
+            KeyCode::Char(typed_char) => {
+                if typing_test.typed_char(typed_char) {
+                    write_journal(typing_test, journal);
+                }
+            }
+            KeyCode::Backspace => typing_test.backspace(),
+            _ => {}
+        }
+    }
+}
+
+fn write_journal(typing_test: &TypingTest, journal: Option<&storage::JournalWriter>) {
+    if let (Some(journal), Some(typing_result)) = (journal, typing_test.result_so_far()) {
+        journal.write(typing_result);
+    }
+}
+
+fn draw<W: Write>(wr: &mut W, typing_test: &TypingTest) -> io::Result<()> {
+    let (width, _) = terminal::size()?;
+    let timer = typing_test
+        .remaining_time_string()
+        .unwrap_or_else(|| String::from("Start typing, Esc to give up"));
+    queue!(
+        wr,
+        cursor::MoveTo(0, TIMER_ROW),
+        SetForegroundColor(Color::Magenta),
+        Print(timer),
+        ResetColor,
+        terminal::Clear(ClearType::UntilNewLine)
+    )?;
+
+    // scroll so the line with the next word is always at the top
+    let next_word = typing_test.words_entered.len();
+    let lines = wrap_words(&typing_test.words, width as usize);
+    let current_line = lines
+        .iter()
+        .position(|line| line.contains(&next_word))
+        .unwrap_or(0);
+    for row in 0..LINES_SHOWN {
+        queue!(wr, cursor::MoveTo(0, FIRST_WORDS_ROW + row))?;
+        if let Some(line) = lines.get(current_line + row as usize) {
+            for word_idx in line.clone() {
+                let color = match typing_test.words_entered.get(word_idx) {
+                    Some(EnteredWord::Correct) => Color::Green,
+                    Some(EnteredWord::Incorrect) => Color::Red,
+                    None if word_idx == next_word => Color::Blue,
+                    None => Color::Reset,
+                };
+                queue!(
+                    wr,
+                    SetForegroundColor(color),
+                    Print(&typing_test.words[word_idx]),
+                    ResetColor,
+                    Print(" ")
+                )?;
+            }
+        }
+        queue!(wr, terminal::Clear(ClearType::UntilNewLine))?;
+    }
+
+    let input_color = if typing_test.correct_so_far() || typing_test.entered_text.is_empty() {
+        Color::Green
+    } else {
+        Color::Red
+    };
+    queue!(
+        wr,
+        cursor::MoveTo(0, INPUT_ROW),
+        Print("> "),
+        SetForegroundColor(input_color),
+        Print(&typing_test.entered_text),
+        ResetColor,
+        terminal::Clear(ClearType::UntilNewLine)
+    )?;
+    wr.flush()
+}
+
+// Splits the words into lines that fit in `width` columns, as ranges of word indices
+fn wrap_words(words: &[String], width: usize) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut line_len = 0;
+    for (word_idx, word) in words.iter().enumerate() {
+        let word_len = word.chars().count() + 1; // and a space
+        if line_len + word_len > width && word_idx > line_start {
+            lines.push(line_start..word_idx);
+            line_start = word_idx;
+            line_len = 0;
+        }
+        line_len += word_len;
+    }
+    if line_start < words.len() {
+        lines.push(line_start..words.len());
+    }
+    lines
+}
+
+// Offers back a test left in the journal by a crash, as the window does
+fn recover_journal(config: &Config, path: &Path) -> Result<(), Box<dyn Error>> {
+    let entry = match storage::read_journal(path)? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+    println!("Recovered a result that wasn't saved:");
+    print_summary(entry.typing_result());
+    save_result(config, entry.typing_result().clone(), Some(path))
+}
+
+fn print_summary(typing_result: &TypingResult) {
+    println!("Words per minute: {}", typing_result.wpm);
+    println!("Correct words:    {}", typing_result.correct_words);
+    println!("Incorrect words:  {}", typing_result.incorrect_words);
+    println!("Backspaces:       {}", typing_result.backspaces);
+    if let Some(consistency) = typing_result.consistency() {
+        println!("Consistency:      {:.0}%", consistency);
+    }
+}
+
+fn prompt(question: &str) -> io::Result<String> {
+    print!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+fn save_result(
+    config: &Config,
+    mut typing_result: TypingResult,
+    journal_path: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    if !config.auto_save.should_save(&typing_result) {
+        if let Some(path) = journal_path {
+            storage::write_journal(
+                path,
+                &storage::JournalEntry::Finished(typing_result.clone()),
+            )?;
+        }
+        let answer = prompt("Save this result? [Y/n] ")?;
+        if answer.to_lowercase().starts_with('n') {
+            if let Some(path) = journal_path {
+                storage::clear_journal(path)?;
+            }
+            return Ok(());
+        }
+        typing_result.tags = parse_tags(&prompt("Tags, separated by commas: ")?);
+    }
+    storage::open_backend(config)?.append(&typing_result)?;
+    if let Some(path) = journal_path {
+        storage::clear_journal(path)?;
+    }
+    println!("Saved");
+    Ok(())
+}
+
+#[test]
+fn test_words_wrap_to_the_terminal_width() {
+    let words = [
+        "the",
+        "quick",
+        "brown",
+        "fox",
+        "jumped",
+        "supercalifragilistic",
+    ]
+    .iter()
+    .map(|word| word.to_string())
+    .collect::<Vec<_>>();
+
+    assert_eq!(vec![0..3, 3..5, 5..6], wrap_words(&words, 16));
+}
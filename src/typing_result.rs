@@ -1,17 +1,49 @@
 use chrono::offset::LocalResult;
-use chrono::prelude::{DateTime, Local};
-use chrono::TimeZone;
+use chrono::prelude::{DateTime, FixedOffset, Local};
+use chrono::{Offset, TimeZone};
+use rand::Rng;
 use std::fmt;
 use std::time::{Duration, SystemTime};
 
 #[derive(Clone, Default, Debug, PartialEq)]
 pub struct TypingResult {
+    pub id: u64, // zero means the result has not been given an ID yet
     pub correct_words: i32,
     pub incorrect_words: i32,
     pub backspaces: i32,
     pub wpm: i32,
     pub time: u64,
     pub notes: String,
+    // Milliseconds since the epoch, zero for results from before they were kept
+    pub started_at_ms: u64,
+    pub ended_at_ms: u64,
+    pub utc_offset_secs: i32, // the local time zone where the test was taken
+    pub tags: Vec<String>,    // normalised with `parse_tags`
+    pub timeline: Vec<WpmSample>, // one a second, empty for older results
+    pub keystrokes: Vec<Keystroke>, // empty for older results
+    pub missed_words: Vec<String>, // the words that were typed wrong, in order
+}
+
+// How fast a test was going at the end of each second of it
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct WpmSample {
+    pub raw_wpm: f32, // everything typed in that second, five characters to a word
+    pub net_wpm: f32, // correct words so far, worked out like the result's WPM
+}
+
+// A key pressed during a test, for finding the weak ones. Backspaces aren't
+// kept, the time they took is part of the next key's interval.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Keystroke {
+    pub expected: Option<char>, // None when typed past the last word
+    pub typed: char,
+    pub interval_ms: u32, // since the key before, zero for the first
+}
+
+impl Keystroke {
+    pub fn is_error(&self) -> bool {
+        self.expected != Some(self.typed)
+    }
 }
 
 impl TypingResult {
@@ -22,36 +54,238 @@ impl TypingResult {
         duration: Duration,
     ) -> Self {
         let wpm = (f64::from(correct_words) / (duration.as_secs() as f64 / 60.0)).floor() as i32;
-        let time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("SystemTime to work!")
-            .as_secs();
+        let ended_at_ms = millis_since_epoch(SystemTime::now());
 
         Self {
+            id: new_result_id(),
             correct_words,
             incorrect_words,
             backspaces,
             wpm,
-            time,
+            time: ended_at_ms / 1000,
             notes: String::new(),
+            started_at_ms: ended_at_ms.saturating_sub(duration.as_millis() as u64),
+            ended_at_ms,
+            utc_offset_secs: Local::now().offset().fix().local_minus_utc(),
+            tags: Vec::new(),
+            timeline: Vec::new(),
+            keystrokes: Vec::new(),
+            missed_words: Vec::new(),
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = normalise_tag(tag);
+        self.tags.contains(&tag)
+    }
+
+    // For showing and editing, the reverse of `parse_tags`
+    pub fn tags_text(&self) -> String {
+        self.tags.join(", ")
+    }
+
+    // Each second's raw and net WPM separated by a slash, the reverse of `parse_timeline`
+    pub fn timeline_text(&self) -> String {
+        self.timeline
+            .iter()
+            .map(|sample| format!("{}/{}", sample.raw_wpm, sample.net_wpm))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Each keystroke as the expected and typed characters' code points in hex
+    // and the interval, separated by colons. Zero stands for no expected
+    // character. The reverse of `parse_keystrokes`.
+    pub fn keystrokes_text(&self) -> String {
+        self.keystrokes
+            .iter()
+            .map(|keystroke| {
+                format!(
+                    "{:x}:{:x}:{}",
+                    keystroke.expected.map_or(0, u32::from),
+                    u32::from(keystroke.typed),
+                    keystroke.interval_ms
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Separated by spaces, which words never contain
+    pub fn missed_words_text(&self) -> String {
+        self.missed_words.join(" ")
+    }
+
+    // When the test started and ended, if the clock was read at the time
+    pub fn set_times(&mut self, started_at: SystemTime, ended_at: SystemTime) {
+        self.started_at_ms = millis_since_epoch(started_at);
+        self.ended_at_ms = millis_since_epoch(ended_at);
+        self.time = self.ended_at_ms / 1000;
+    }
+
+    // How long the test took, if its start and end were kept
+    pub fn duration(&self) -> Option<Duration> {
+        if self.started_at_ms == 0 || self.ended_at_ms < self.started_at_ms {
+            return None;
+        }
+        Some(Duration::from_millis(self.ended_at_ms - self.started_at_ms))
+    }
+
+    // How evenly paced the typing was, from 100% when the raw speed was the
+    // same every second down to 0% when it varied as much as its mean
+    pub fn consistency(&self) -> Option<f64> {
+        let speeds = self
+            .timeline
+            .iter()
+            .map(|sample| f64::from(sample.raw_wpm))
+            .collect::<Vec<_>>();
+        if speeds.len() < 2 {
+            return None;
+        }
+        let mean = speeds.iter().sum::<f64>() / speeds.len() as f64;
+        if mean <= 0.0 {
+            return None;
+        }
+        let variance =
+            speeds.iter().map(|wpm| (wpm - mean).powi(2)).sum::<f64>() / speeds.len() as f64;
+        Some((100.0 * (1.0 - variance.sqrt() / mean)).max(0.0))
+    }
+
+    // For putting results in order, tests in the same second included
+    pub fn timestamp_ms(&self) -> u64 {
+        if self.ended_at_ms != 0 {
+            self.ended_at_ms
+        } else {
+            self.time * 1000
         }
     }
 
-    pub fn datetime(&self) -> Option<DateTime<Local>> {
+    // The same test can turn up more than once with different IDs, e.g. when
+    // a result has been round-tripped through a spreadsheet
+    pub fn is_duplicate_of(&self, other: &TypingResult) -> bool {
+        (self.id != 0 && self.id == other.id)
+            || (self.time != 0
+                && self.time == other.time
+                && self.correct_words == other.correct_words
+                && self.incorrect_words == other.incorrect_words
+                && self.backspaces == other.backspaces
+                && self.wpm == other.wpm)
+    }
+
+    // In the time zone the test was taken in. Older results didn't keep it,
+    // so they're shown in the current one.
+    pub fn datetime(&self) -> Option<DateTime<FixedOffset>> {
+        if self.ended_at_ms != 0 {
+            let offset = FixedOffset::east_opt(self.utc_offset_secs)?;
+            let millis = self.ended_at_ms as i64;
+            return match offset.timestamp_opt(millis / 1000, (millis % 1000) as u32 * 1_000_000) {
+                LocalResult::Single(value) => Some(value),
+                _ => None,
+            };
+        }
         if self.time == 0 {
             return None;
         }
         match Local.timestamp_opt(self.time as i64, 0) {
-            LocalResult::Single(value) => Some(value),
+            LocalResult::Single(value) => Some(value.with_timezone(value.offset())),
             _ => None,
         }
     }
 }
 
+// Splits candidates into the results we don't have yet, and a count of the duplicates
+pub fn unseen_results(
+    existing: &[TypingResult],
+    candidates: Vec<TypingResult>,
+) -> (Vec<TypingResult>, usize) {
+    let mut unseen: Vec<TypingResult> = Vec::new();
+    let mut duplicates = 0;
+    for typing_result in candidates {
+        let seen = existing
+            .iter()
+            .chain(unseen.iter())
+            .any(|other| typing_result.is_duplicate_of(other));
+        if seen {
+            duplicates += 1;
+        } else {
+            unseen.push(typing_result);
+        }
+    }
+    (unseen, duplicates)
+}
+
+// Tags are typed separated by commas. They're compared ignoring case and
+// surrounding spaces, and each one is only kept once.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split([',', '\n']).map(normalise_tag) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+pub fn parse_timeline(text: &str) -> Result<Vec<WpmSample>, String> {
+    text.split_whitespace()
+        .map(|sample| {
+            let mut speeds = sample.splitn(2, '/').map(str::parse::<f32>);
+            match (speeds.next(), speeds.next()) {
+                (Some(Ok(raw_wpm)), Some(Ok(net_wpm))) => Ok(WpmSample { raw_wpm, net_wpm }),
+                _ => Err(format!("Bad timeline sample {:?}", sample)),
+            }
+        })
+        .collect()
+}
+
+pub fn parse_keystrokes(text: &str) -> Result<Vec<Keystroke>, String> {
+    text.split_whitespace()
+        .map(|keystroke| {
+            let bad_keystroke = || format!("Bad keystroke {:?}", keystroke);
+            let mut fields = keystroke.splitn(3, ':');
+            let mut code_point = || {
+                fields
+                    .next()
+                    .and_then(|field| u32::from_str_radix(field, 16).ok())
+                    .ok_or_else(bad_keystroke)
+            };
+            let expected = code_point()?;
+            let typed = code_point()?;
+            let interval_ms = fields
+                .next()
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(bad_keystroke)?;
+            Ok(Keystroke {
+                expected: if expected == 0 {
+                    None
+                } else {
+                    Some(std::char::from_u32(expected).ok_or_else(bad_keystroke)?)
+                },
+                typed: std::char::from_u32(typed).ok_or_else(bad_keystroke)?,
+                interval_ms,
+            })
+        })
+        .collect()
+}
+
+pub fn normalise_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+pub fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .expect("SystemTime to work!")
+        .as_millis() as u64
+}
+
+pub fn new_result_id() -> u64 {
+    rand::thread_rng().gen_range(1, u64::MAX)
+}
+
 impl fmt::Display for TypingResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let datetime = if let Some(local) = self.datetime() {
-            format!("{}", local)
+            format!("{}", local.format("%Y-%m-%d %H:%M:%S%.3f %:z"))
         } else {
             format!("NO DATETIME")
         };
@@ -59,6 +293,74 @@ impl fmt::Display for TypingResult {
             f,
             "Result: [{}], {:3}wpm (correct words: {:3}, incorrect words: {:3}, backspaces: {:3})",
             datetime, self.wpm, self.correct_words, self.incorrect_words, self.backspaces
-        )
+        )?;
+        if !self.tags.is_empty() {
+            write!(f, " tags: {}", self.tags_text())?;
+        }
+        Ok(())
     }
 }
+
+#[test]
+fn test_datetime_is_in_the_zone_the_test_was_taken_in() {
+    let typing_result = TypingResult {
+        time: 1556223259,
+        started_at_ms: 1556223199250,
+        ended_at_ms: 1556223259250,
+        utc_offset_secs: 9 * 3600,
+        ..TypingResult::default()
+    };
+    let next_result = TypingResult {
+        time: 1556223259,
+        ended_at_ms: 1556223259900,
+        ..typing_result.clone()
+    };
+
+    assert_eq!(
+        "2019-04-26 05:14:19.250 +09:00",
+        typing_result
+            .datetime()
+            .unwrap()
+            .format("%Y-%m-%d %H:%M:%S%.3f %:z")
+            .to_string()
+    );
+    assert!(next_result.timestamp_ms() > typing_result.timestamp_ms());
+}
+
+#[test]
+fn test_tags_are_parsed_from_a_list() {
+    let typing_result = TypingResult {
+        tags: parse_tags(" Ergodox, tired,,warmup, TIRED "),
+        ..TypingResult::default()
+    };
+
+    assert_eq!(vec!["ergodox", "tired", "warmup"], typing_result.tags);
+    assert!(typing_result.has_tag("Tired"));
+    assert!(!typing_result.has_tag("rested"));
+    assert_eq!("ergodox, tired, warmup", typing_result.tags_text());
+}
+
+#[test]
+fn test_consistency_is_how_little_the_raw_speed_varied() {
+    let sample = |raw_wpm| WpmSample {
+        raw_wpm,
+        net_wpm: 0.0,
+    };
+    let steady = TypingResult {
+        timeline: vec![sample(60.0); 5],
+        ..TypingResult::default()
+    };
+    let uneven = TypingResult {
+        timeline: vec![sample(40.0), sample(80.0), sample(40.0), sample(80.0)],
+        ..TypingResult::default()
+    };
+    let erratic = TypingResult {
+        timeline: vec![sample(0.0), sample(0.0), sample(0.0), sample(120.0)],
+        ..TypingResult::default()
+    };
+
+    assert_eq!(Some(100.0), steady.consistency());
+    assert_eq!(Some(100.0 * (1.0 - 20.0 / 60.0)), uneven.consistency());
+    assert_eq!(Some(0.0), erratic.consistency());
+    assert_eq!(None, TypingResult::default().consistency());
+}
@@ -1,10 +1,12 @@
 use crate::words;
 use crate::*;
+#[cfg(feature = "gui")]
 use cgmath::Vector2;
+#[cfg(feature = "gui")]
 use gfx_glyph::{FontId, OwnedSectionText, OwnedVariedSection, Scale};
 use rand;
 use rand::seq::SliceRandom;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 const SAMPLE_WORDS: usize = 300; // num of words to sample - should be less than highest WPM
 
@@ -23,10 +25,19 @@ pub struct TypingTest {
     pub backspaces: i32,
     pub start_time: Option<Instant>,
     pub end_time: Option<Instant>,
+    // wall clock times for the result, Instants only measure the duration
+    started_at: Option<SystemTime>,
+    ended_at: Option<SystemTime>,
     pub duration: Option<Duration>,
     pub ended: bool,
     pub word_colors: Vec<ColorArray>,
     skip_num: usize,
+    // how far into the test each character was typed and each word entered
+    typed_times: Vec<Duration>,
+    word_times: Vec<Duration>,
+    keystrokes: Vec<Keystroke>,
+    last_key_time: Option<Instant>,
+    missed_words: Vec<String>,
 }
 
 impl TypingTest {
@@ -69,9 +80,18 @@ impl TypingTest {
     pub fn typed_char(&mut self, typed_char: char) -> bool {
         let mut word_ended = false;
         if !self.ended {
+            let keystroke = Keystroke {
+                expected: self.expected_char(),
+                typed: typed_char,
+                interval_ms: self.key_interval_ms(),
+            };
+            self.keystrokes.push(keystroke);
             self.entered_text.push(typed_char);
             let num_words = self.words_entered.len();
             self.update();
+            if let Some(start_time) = self.start_time {
+                self.typed_times.push(start_time.elapsed());
+            }
             if self.words_entered.len() > num_words {
                 word_ended = true;
             }
@@ -81,11 +101,29 @@ impl TypingTest {
 
     pub fn backspace(&mut self) {
         if !self.ended && self.entered_text.pop().is_some() {
+            let _ = self.key_interval_ms();
             self.backspaces += 1;
             self.update();
         }
     }
 
+    // The character that should be typed next, a space at the end of a word
+    fn expected_char(&self) -> Option<char> {
+        let word = self.words.get(self.next_word)?;
+        let typed_len = self.entered_text.chars().count();
+        Some(word.chars().nth(typed_len).unwrap_or(' '))
+    }
+
+    // Milliseconds since the last key, and restarts the count
+    fn key_interval_ms(&mut self) -> u32 {
+        let now = Instant::now();
+        let interval = self
+            .last_key_time
+            .map_or(0, |last_key_time| (now - last_key_time).as_millis() as u32);
+        self.last_key_time = Some(now);
+        interval
+    }
+
     fn update(&mut self) {
         if !self.ended && self.start_time.is_none() {
             self.start();
@@ -108,6 +146,15 @@ impl TypingTest {
                         EnteredWord::Incorrect
                     };
                 self.words_entered.push(assessment);
+                if assessment == EnteredWord::Incorrect {
+                    if let Some(word) = self.words.get(self.next_word) {
+                        self.missed_words.push(word.clone());
+                    }
+                }
+                self.word_times.push(
+                    self.start_time
+                        .map_or(Duration::default(), |start| start.elapsed()),
+                );
                 self.word_colors[self.next_word] = if assessment == EnteredWord::Correct {
                     CORRECT_WORD_COLOR
                 } else {
@@ -136,15 +183,17 @@ impl TypingTest {
 
     pub fn start(&mut self) {
         self.start_time = Some(Instant::now());
+        self.started_at = Some(SystemTime::now());
     }
 
     pub fn end(&mut self) {
         self.update();
         self.end_time = Some(Instant::now());
+        self.ended_at = Some(SystemTime::now());
         self.ended = true;
     }
 
-    pub fn result(&self) -> TypingResult {
+    fn word_counts(&self) -> (i32, i32) {
         let mut correct_words = 0;
         let mut incorrect_words = 0;
         for word in &self.words_entered {
@@ -153,12 +202,76 @@ impl TypingTest {
                 EnteredWord::Incorrect => incorrect_words += 1,
             };
         }
-        TypingResult::new(
+        (correct_words, incorrect_words)
+    }
+
+    pub fn result(&self) -> TypingResult {
+        let (correct_words, incorrect_words) = self.word_counts();
+        let mut typing_result = TypingResult::new(
             correct_words,
             incorrect_words,
             self.backspaces,
             self.duration.unwrap(),
-        )
+        );
+        if let (Some(started_at), Some(ended_at)) = (self.started_at, self.ended_at) {
+            typing_result.set_times(started_at, ended_at);
+        }
+        typing_result.timeline = self.timeline(self.duration.unwrap());
+        typing_result.keystrokes = self.keystrokes.clone();
+        typing_result.missed_words = self.missed_words.clone();
+        typing_result
+    }
+
+    // The result if the test were to end now, None until a second has gone
+    pub fn result_so_far(&self) -> Option<TypingResult> {
+        let elapsed = self.start_time?.elapsed();
+        if elapsed.as_secs() == 0 {
+            return None;
+        }
+        let (correct_words, incorrect_words) = self.word_counts();
+        let mut typing_result =
+            TypingResult::new(correct_words, incorrect_words, self.backspaces, elapsed);
+        if let Some(started_at) = self.started_at {
+            typing_result.set_times(started_at, SystemTime::now());
+        }
+        typing_result.timeline = self.timeline(elapsed);
+        typing_result.keystrokes = self.keystrokes.clone();
+        typing_result.missed_words = self.missed_words.clone();
+        Some(typing_result)
+    }
+
+    // A sample for each whole second of `elapsed`. Anything after the last
+    // one, like the word being typed when the time ran out, counts towards it.
+    fn timeline(&self, elapsed: Duration) -> Vec<WpmSample> {
+        let seconds = elapsed.as_secs() as usize;
+        if seconds == 0 {
+            return Vec::new();
+        }
+        let second_of = |time: &Duration| (time.as_secs() as usize).min(seconds - 1);
+        let mut typed = vec![0; seconds];
+        for time in &self.typed_times {
+            typed[second_of(time)] += 1;
+        }
+        let mut correct = vec![0; seconds];
+        for (word, time) in self.words_entered.iter().zip(&self.word_times) {
+            if *word == EnteredWord::Correct {
+                correct[second_of(time)] += 1;
+            }
+        }
+
+        let mut correct_so_far = 0;
+        typed
+            .iter()
+            .zip(&correct)
+            .enumerate()
+            .map(|(second, (&typed, &correct))| {
+                correct_so_far += correct;
+                WpmSample {
+                    raw_wpm: typed as f32 / 5.0 * 60.0,
+                    net_wpm: correct_so_far as f32 * 60.0 / (second + 1) as f32,
+                }
+            })
+            .collect()
     }
 
     pub fn set_words(&mut self, words: Vec<String>) {
@@ -175,6 +288,7 @@ impl TypingTest {
         self.words.join(" ")
     }
 
+    #[cfg(feature = "gui")]
     pub fn words_as_sections(&self, font_id: FontId, scale: f32) -> Vec<OwnedSectionText> {
         let mut sections = vec![];
         for (word_idx, word) in self.words.iter().enumerate().skip(self.skip_num) {
@@ -196,6 +310,7 @@ impl TypingTest {
         sections
     }
 
+    #[cfg(feature = "gui")]
     pub fn words_as_varied_section(
         &self,
         bounds: Vector2<f32>,
@@ -222,4 +337,83 @@ impl TypingTest {
             .collect();
         self.set_words(test_words);
     }
+
+    // Words weighted towards the keys, bigrams and words that the results
+    // show need practice
+    pub fn practice(&mut self, results: &[TypingResult]) {
+        let weaknesses = practice::Weaknesses::from_results(results);
+        let test_words = practice::practice_words(
+            &weaknesses,
+            &weaknesses.candidates(),
+            SAMPLE_WORDS,
+            &mut rand::thread_rng(),
+        );
+        self.set_words(test_words);
+    }
+}
+
+#[test]
+fn test_timeline_samples_each_second() {
+    let second = |secs: f64| Duration::from_secs_f64(secs);
+    let typing_test = TypingTest {
+        words_entered: vec![
+            EnteredWord::Correct,
+            EnteredWord::Incorrect,
+            EnteredWord::Correct,
+        ],
+        // the last word is entered as the time runs out
+        word_times: vec![second(0.8), second(1.5), second(3.01)],
+        typed_times: [0.1, 0.2, 0.3, 0.4, 0.5, 0.8, 1.2, 1.5, 2.9, 3.0]
+            .iter()
+            .map(|&secs| second(secs))
+            .collect(),
+        ..TypingTest::default()
+    };
+
+    let raw = |sample: &WpmSample| sample.raw_wpm;
+    let net = |sample: &WpmSample| sample.net_wpm;
+    let timeline = typing_test.timeline(Duration::from_secs(3));
+    assert_eq!(
+        vec![72.0, 24.0, 24.0],
+        timeline.iter().map(raw).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![60.0, 30.0, 40.0],
+        timeline.iter().map(net).collect::<Vec<_>>()
+    );
+    assert!(typing_test.timeline(Duration::from_millis(900)).is_empty());
+}
+
+#[test]
+fn test_keystrokes_are_checked_against_the_words() {
+    let mut typing_test = TypingTest::default();
+    typing_test.set_words(vec![String::from("to"), String::from("be")]);
+    for typed_char in "tp bee x".chars() {
+        typing_test.typed_char(typed_char);
+    }
+    typing_test.backspace();
+
+    let keys = typing_test
+        .keystrokes
+        .iter()
+        .map(|keystroke| (keystroke.expected, keystroke.typed, keystroke.is_error()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            (Some('t'), 't', false),
+            (Some('o'), 'p', true),
+            (Some(' '), ' ', false),
+            (Some('b'), 'b', false),
+            (Some('e'), 'e', false),
+            (Some(' '), 'e', true),
+            (Some(' '), ' ', false),
+            (None, 'x', true),
+        ],
+        keys
+    );
+    assert_eq!(0, typing_test.keystrokes[0].interval_ms);
+    assert_eq!(
+        vec![String::from("to"), String::from("be")],
+        typing_test.missed_words
+    );
 }